@@ -39,4 +39,8 @@ impl BrightnessBackend for Blight {
 		let val = val.max(min);
 		Ok(self.device.write_value(val)?)
 	}
+
+	fn set_raw(&mut self, val: u32) -> anyhow::Result<()> {
+		Ok(self.device.write_value(val.min(self.device.max()))?)
+	}
 }
@@ -1,4 +1,4 @@
-use super::{BrightnessBackend, BrightnessBackendConstructor};
+use super::{div_round_u32, BrightnessBackend, BrightnessBackendConstructor};
 
 const EXPECT_STR: &str = "VirtualDevice didn't test the command during initialization";
 
@@ -171,8 +171,12 @@ impl BrightnessBackend for BrightnessCtl {
 		let raw_val = div_round_u32(val.max(min) * max, 100);
 		self.device.set_raw(raw_val)
 	}
-}
 
-fn div_round_u32(a: u32, b: u32) -> u32 {
-	(a + b / 2) / b
+	fn set_raw(&mut self, val: u32) -> anyhow::Result<()> {
+		self.device.set_raw(val)
+	}
+
+	fn supports_fading(&self) -> bool {
+		false
+	}
 }
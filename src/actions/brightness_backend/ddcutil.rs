@@ -0,0 +1,537 @@
+use anyhow::{anyhow, bail, Context};
+use gtk::{gio, glib};
+use std::{cell::RefCell, path::Path, process::Command, rc::Rc, sync::Arc};
+
+use super::BrightnessBackend;
+
+/// The default ddcutil program
+pub(super) const DDCUTIL_PROGRAM: &str = "ddcutil";
+
+/// The VCP feature code of the display brightness
+const VCP_BRIGHTNESS: &str = "10";
+
+/// Runs ddcutil and returns its output. Blocks for up to a few hundred
+/// milliseconds, so only use it outside of the main thread
+fn run(program: &Path, bus: Option<u32>, args: &[String]) -> anyhow::Result<String> {
+	let mut cmd = Command::new(program);
+	if let Some(bus) = bus {
+		cmd.arg("--bus").arg(bus.to_string());
+	}
+	let output = cmd.args(args).output()?;
+	if !output.status.success() {
+		bail!(
+			"ddcutil {} failed: {}",
+			args.join(" "),
+			String::from_utf8_lossy(&output.stderr).trim()
+		);
+	}
+	Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Starts ddcutil on a worker thread. The output can be awaited on the main loop
+fn run_in_background(
+	program: Arc<Path>,
+	bus: Option<u32>,
+	args: &[&str],
+) -> impl Future<Output = anyhow::Result<String>> + use<> {
+	let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+	let handle = gio::spawn_blocking(move || run(&program, bus, &args));
+	async move {
+		handle
+			.await
+			.unwrap_or_else(|_| Err(anyhow!("ddcutil panicked")))
+	}
+}
+
+#[derive(Default)]
+struct DdcMonitorState {
+	/// The current and max brightness, once read
+	brightness: Option<(u32, u32)>,
+	/// The brightness to set once ddcutil isn't running for the monitor anymore
+	pending: Option<u32>,
+	/// Whether ddcutil is running for the monitor
+	busy: bool,
+	/// Whether the last ddcutil command failed, ex: after unplugging the monitor
+	failed: bool,
+}
+
+/// A DDC/CI monitor on an I2C bus. ddcutil runs in the background, one
+/// command at a time, and the brightness is remembered in between
+#[derive(Clone)]
+pub(super) struct DdcMonitor {
+	program: Arc<Path>,
+	bus: u32,
+	state: Rc<RefCell<DdcMonitorState>>,
+}
+
+impl DdcMonitor {
+	/// Starts reading the brightness of the monitor
+	fn new(program: Arc<Path>, bus: u32) -> Self {
+		let monitor = Self {
+			program,
+			bus,
+			state: Rc::default(),
+		};
+		monitor.refresh();
+		monitor
+	}
+
+	/// Reads the brightness again in the background, unless ddcutil is
+	/// already running for the monitor
+	fn refresh(&self) {
+		if self.state.borrow().busy {
+			return;
+		}
+		self.spawn(&["getvcp", VCP_BRIGHTNESS, "--brief"], |monitor, output| {
+			let mut state = monitor.state.borrow_mut();
+			match output.and_then(|output| parse_getvcp_brief(&output)) {
+				// Don't overwrite the brightness that's about to be set
+				Ok(_) if state.pending.is_some() => {}
+				Ok(brightness) => state.brightness = Some(brightness),
+				Err(e) => {
+					eprintln!(
+						"Could not read the brightness of the DDC/CI monitor on bus {}: {}",
+						monitor.bus, e
+					);
+					state.failed = true;
+				}
+			}
+		});
+	}
+
+	/// Sets the brightness in the background. Only the latest brightness is
+	/// set after the running ddcutil command, so that changes don't pile up
+	fn set(&self, val: u32) {
+		let mut state = self.state.borrow_mut();
+		if let Some((current, _)) = &mut state.brightness {
+			*current = val;
+		}
+		state.pending = Some(val);
+		drop(state);
+		self.set_pending();
+	}
+
+	fn set_pending(&self) {
+		let mut state = self.state.borrow_mut();
+		if state.busy {
+			return;
+		}
+		let Some(val) = state.pending.take() else {
+			return;
+		};
+		drop(state);
+		self.spawn(
+			&["setvcp", VCP_BRIGHTNESS, &val.to_string()],
+			|monitor, output| {
+				if let Err(e) = output {
+					eprintln!(
+						"Could not change the brightness of the DDC/CI monitor on bus {}: {}",
+						monitor.bus, e
+					);
+					monitor.state.borrow_mut().failed = true;
+				}
+			},
+		);
+	}
+
+	/// Runs ddcutil for the monitor on a worker thread, and then `done` with
+	/// its output on the main loop
+	fn spawn(&self, args: &[&str], done: impl FnOnce(&Self, anyhow::Result<String>) + 'static) {
+		let mut state = self.state.borrow_mut();
+		state.busy = true;
+		state.failed = false;
+		drop(state);
+
+		let output = run_in_background(self.program.clone(), Some(self.bus), args);
+		let monitor = self.clone();
+		glib::spawn_future_local(async move {
+			let output = output.await;
+			monitor.state.borrow_mut().busy = false;
+			done(&monitor, output);
+			monitor.set_pending();
+		});
+	}
+}
+
+#[derive(Default)]
+enum DdcDetection {
+	#[default]
+	Unknown,
+	Running,
+	/// The connector and monitor of each detected monitor
+	Done(Vec<(String, DdcMonitor)>),
+}
+
+/// Detects the DDC/CI monitors in the background, since it takes a few seconds
+pub(super) struct DdcMonitors {
+	program: Arc<Path>,
+	detection: Rc<RefCell<DdcDetection>>,
+}
+
+impl DdcMonitors {
+	pub(super) fn new(program: &Path) -> Self {
+		Self {
+			program: program.into(),
+			detection: Rc::default(),
+		}
+	}
+
+	/// Detects the monitors again in the background, unless it's already running
+	pub(super) fn detect(&self) {
+		let mut detection = self.detection.borrow_mut();
+		if let DdcDetection::Running = *detection {
+			return;
+		}
+		*detection = DdcDetection::Running;
+		drop(detection);
+
+		let program = self.program.clone();
+		let output = run_in_background(program.clone(), None, &["detect", "--terse"]);
+		let detection = self.detection.clone();
+		glib::spawn_future_local(async move {
+			let displays = output.await.and_then(|output| parse_detect_terse(&output));
+			*detection.borrow_mut() = match displays {
+				Ok(displays) => DdcDetection::Done(
+					displays
+						.into_iter()
+						.map(|(bus, name)| (name, DdcMonitor::new(program.clone(), bus)))
+						.collect(),
+				),
+				Err(e) => {
+					eprintln!("Could not detect DDC/CI monitors: {}", e);
+					DdcDetection::Unknown
+				}
+			};
+		});
+	}
+
+	/// Returns the connector and monitor of each detected monitor. Starts
+	/// detecting them and fails when they aren't detected yet
+	pub(super) fn get(&self) -> anyhow::Result<Vec<(String, DdcMonitor)>> {
+		match &*self.detection.borrow() {
+			DdcDetection::Done(monitors) => return Ok(monitors.clone()),
+			DdcDetection::Running => bail!("The DDC/CI monitors are still being detected"),
+			DdcDetection::Unknown => {}
+		}
+		self.detect();
+		bail!("The DDC/CI monitors are still being detected")
+	}
+
+	/// Returns the monitor connected to the connector, or the first monitor.
+	/// The monitors are detected again if it's unknown, ex: after plugging it in
+	pub(super) fn find(&self, connector: Option<&str>) -> anyhow::Result<DdcMonitor> {
+		let monitor = self
+			.get()?
+			.into_iter()
+			.find(|(name, _)| connector.is_none_or(|connector| name == connector))
+			.map(|(_, monitor)| monitor);
+		if monitor.is_none() {
+			self.detect();
+		}
+		monitor.with_context(|| match connector {
+			Some(connector) => format!("No DDC/CI monitor found on connector '{}'", connector),
+			None => "No DDC/CI monitor found".to_string(),
+		})
+	}
+}
+
+/// Changes the brightness of external monitors over DDC/CI by driving `ddcutil`.
+/// The device name is the monitor connector, ex: "DP-1".
+/// Note: The brightness is changed in the background, so errors are only logged
+pub(super) struct DdcUtil {
+	monitor: DdcMonitor,
+	current: u32,
+	max: u32,
+}
+
+impl DdcUtil {
+	/// Uses the last read brightness of the monitor, and reads it again in
+	/// the background for the next time
+	pub(super) fn new(monitor: &DdcMonitor) -> anyhow::Result<Self> {
+		let state = monitor.state.borrow();
+		let brightness = state.brightness;
+		let failed = state.failed;
+		drop(state);
+		monitor.refresh();
+
+		if failed {
+			bail!("The DDC/CI monitor on bus {} didn't respond", monitor.bus);
+		}
+		let (current, max) = brightness.with_context(|| {
+			format!(
+				"The brightness of the DDC/CI monitor on bus {} wasn't read yet",
+				monitor.bus
+			)
+		})?;
+		Ok(Self {
+			monitor: monitor.clone(),
+			current,
+			max,
+		})
+	}
+}
+
+/// Parses the I2C bus and connector of each monitor in the output of
+/// `ddcutil detect --terse`. Ex:
+/// ```text
+/// Display 1
+///    I2C bus:  /dev/i2c-4
+///    DRM connector:  card0-DP-1
+/// ```
+fn parse_detect_terse(output: &str) -> anyhow::Result<Vec<(u32, String)>> {
+	let mut displays = Vec::new();
+	let mut bus = None;
+	for line in output.lines() {
+		let Some((key, value)) = line.split_once(':') else {
+			continue;
+		};
+		match key.trim() {
+			"I2C bus" => {
+				bus = value
+					.trim()
+					.strip_prefix("/dev/i2c-")
+					.and_then(|bus| bus.parse::<u32>().ok());
+			}
+			"DRM connector" => {
+				// Strip the "cardN-" prefix
+				let value = value.trim();
+				let name = value.split_once('-').map_or(value, |(_, name)| name);
+				let bus = bus.take().context("Could not parse the I2C bus")?;
+				displays.push((bus, name.to_string()));
+			}
+			_ => {}
+		}
+	}
+	Ok(displays)
+}
+
+/// Parses the current and max brightness in the output of
+/// `ddcutil getvcp 10 --brief`. Ex: "VCP 10 C 50 100"
+fn parse_getvcp_brief(output: &str) -> anyhow::Result<(u32, u32)> {
+	let values: Vec<u32> = output
+		.split_whitespace()
+		.skip(3)
+		.filter_map(|value| value.parse().ok())
+		.collect();
+	match values[..] {
+		[current, max] => Ok((current, max)),
+		_ => bail!("Could not parse the ddcutil output: {}", output.trim()),
+	}
+}
+
+impl BrightnessBackend for DdcUtil {
+	fn get_current(&mut self) -> u32 {
+		self.current
+	}
+
+	fn get_max(&mut self) -> u32 {
+		self.max
+	}
+
+	fn set_raw(&mut self, val: u32) -> anyhow::Result<()> {
+		let val = val.min(self.max);
+		self.monitor.set(val);
+		self.current = val;
+		Ok(())
+	}
+
+	fn supports_fading(&self) -> bool {
+		false
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::actions::brightness_backend::BrightnessBackendCache;
+	use crate::config::user::BrightnessBackendType;
+	use std::{
+		fs,
+		os::unix::fs::PermissionsExt,
+		path::PathBuf,
+		thread::sleep,
+		time::{Duration, Instant},
+	};
+
+	const DETECT_OUTPUT: &str = "Display 1
+   I2C bus:  /dev/i2c-4
+   DRM connector:  card0-DP-1
+   Monitor:  DEL:DELL U2415:ABC123
+
+Display 2
+   I2C bus:  /dev/i2c-7
+   DRM connector:  card1-HDMI-A-1
+   Monitor:  GSM:LG ULTRAFINE:123
+
+Invalid display
+   I2C bus:  /dev/i2c-9
+   Monitor:  XYZ:Unknown:
+";
+
+	/// Writes a fake ddcutil, which logs its arguments and stores the brightness
+	/// in the directory. Returns its path
+	fn fake_ddcutil(test_name: &str) -> PathBuf {
+		let dir = std::env::temp_dir().join(format!(
+			"swayosd-ddcutil-{}-{}",
+			test_name,
+			std::process::id()
+		));
+		fs::create_dir_all(&dir).unwrap();
+		fs::write(dir.join("detect"), DETECT_OUTPUT).unwrap();
+		fs::write(dir.join("brightness"), "50").unwrap();
+		let script = format!(
+			r#"#!/bin/sh
+dir="{}"
+echo "$*" >> "$dir/calls"
+case "$*" in
+	"detect --terse") cat "$dir/detect" ;;
+	"--bus 4 getvcp 10 --brief") echo "VCP 10 C $(cat "$dir/brightness") 100" ;;
+	"--bus 4 setvcp 10 "*) echo "$5" > "$dir/brightness" ;;
+	*) echo "Display not found" >&2; exit 1 ;;
+esac
+"#,
+			dir.display()
+		);
+		let program = dir.join("ddcutil");
+		fs::write(&program, script).unwrap();
+		fs::set_permissions(&program, fs::Permissions::from_mode(0o755)).unwrap();
+		program
+	}
+
+	fn count_calls(program: &Path, args: &str) -> usize {
+		let calls = fs::read_to_string(program.with_file_name("calls")).unwrap_or_default();
+		calls.lines().filter(|line| *line == args).count()
+	}
+
+	#[test]
+	fn parses_detect_terse() {
+		assert_eq!(
+			parse_detect_terse(DETECT_OUTPUT).unwrap(),
+			vec![(4, "DP-1".to_string()), (7, "HDMI-A-1".to_string())]
+		);
+		assert!(parse_detect_terse("").unwrap().is_empty());
+		assert!(parse_detect_terse("   DRM connector:  card0-DP-1").is_err());
+	}
+
+	#[test]
+	fn parses_getvcp_brief() {
+		assert_eq!(parse_getvcp_brief("VCP 10 C 50 100\n").unwrap(), (50, 100));
+		assert!(parse_getvcp_brief("VCP 10 ERR").is_err());
+		assert!(parse_getvcp_brief("").is_err());
+	}
+
+	/// Runs the test with its own main loop, which runs the ddcutil results
+	fn with_main_loop(test: impl FnOnce()) {
+		glib::MainContext::new().with_thread_default(test).unwrap();
+	}
+
+	/// Runs the main loop until the condition is true
+	fn wait_until(condition: impl Fn() -> bool) {
+		let context = glib::MainContext::ref_thread_default();
+		let start = Instant::now();
+		while !condition() {
+			assert!(start.elapsed() < Duration::from_secs(5));
+			if !context.iteration(false) {
+				sleep(Duration::from_millis(5));
+			}
+		}
+	}
+
+	fn is_idle(monitor: &DdcMonitor) -> bool {
+		let state = monitor.state.borrow();
+		!state.busy && state.pending.is_none()
+	}
+
+	#[test]
+	fn changes_brightness_in_background() {
+		with_main_loop(|| {
+			let program = fake_ddcutil("change");
+			let monitor = DdcMonitor::new(program.as_path().into(), 4);
+			assert!(DdcUtil::new(&monitor).is_err());
+			wait_until(|| is_idle(&monitor));
+
+			let mut ddcutil = DdcUtil::new(&monitor).unwrap();
+			assert_eq!((ddcutil.get_current(), ddcutil.get_max()), (50, 100));
+			ddcutil.raise(10, 0).unwrap();
+			assert_eq!(ddcutil.get_current(), 60);
+			ddcutil.set(1, 5).unwrap();
+			ddcutil.raise(10, 0).unwrap();
+			assert_eq!(DdcUtil::new(&monitor).unwrap().get_current(), 15);
+			// Only the last brightness is set after reading it again
+			wait_until(|| is_idle(&monitor));
+			assert_eq!(count_calls(&program, "--bus 4 getvcp 10 --brief"), 2);
+			assert_eq!(count_calls(&program, "--bus 4 setvcp 10 60"), 0);
+			assert_eq!(count_calls(&program, "--bus 4 setvcp 10 5"), 0);
+			assert_eq!(count_calls(&program, "--bus 4 setvcp 10 15"), 1);
+			let brightness = fs::read_to_string(program.with_file_name("brightness")).unwrap();
+			assert_eq!(brightness.trim(), "15");
+
+			// Changed on the monitor itself
+			fs::write(program.with_file_name("brightness"), "70").unwrap();
+			DdcUtil::new(&monitor).unwrap();
+			wait_until(|| is_idle(&monitor));
+			assert_eq!(DdcUtil::new(&monitor).unwrap().get_current(), 70);
+			wait_until(|| is_idle(&monitor));
+
+			let monitor = DdcMonitor::new(program.as_path().into(), 7);
+			wait_until(|| is_idle(&monitor));
+			assert!(DdcUtil::new(&monitor).is_err());
+			wait_until(|| is_idle(&monitor));
+
+			fs::remove_dir_all(program.parent().unwrap()).unwrap();
+		});
+	}
+
+	#[test]
+	fn detects_monitors_once() {
+		with_main_loop(|| {
+			let program = fake_ddcutil("cache");
+			let mut cache = BrightnessBackendCache {
+				ddc_monitors: DdcMonitors::new(&program),
+				..Default::default()
+			};
+			let backends = [BrightnessBackendType::DdcUtil];
+			let is_detected =
+				|cache: &BrightnessBackendCache| match &*cache.ddc_monitors.detection.borrow() {
+					DdcDetection::Done(monitors) => {
+						monitors.iter().all(|(_, monitor)| is_idle(monitor))
+					}
+					_ => false,
+				};
+
+			cache.detect_ddc_monitors();
+			assert!(cache
+				.get_preferred_backend(Some("DP-1".to_string()), &backends)
+				.is_err());
+			wait_until(|| is_detected(&cache));
+			for _ in 0..3 {
+				let mut backend = cache
+					.get_preferred_backend(Some("DP-1".to_string()), &backends)
+					.unwrap();
+				assert_eq!(backend.get_percent(), 50);
+			}
+			// The first monitor is used without a connector
+			let mut backend = cache.get_preferred_backend(None, &backends).unwrap();
+			assert_eq!(backend.get_percent(), 50);
+			wait_until(|| is_detected(&cache));
+
+			// Only the monitor on bus 4 responds
+			let ddc_backends = cache.get_ddc_backends();
+			assert_eq!(ddc_backends.len(), 2);
+			assert_eq!(ddc_backends[0].0, "DP-1");
+			assert!(ddc_backends[0].1.is_ok());
+			assert!(ddc_backends[1].1.is_err());
+			wait_until(|| is_detected(&cache));
+
+			// Only detected again for the unknown connector
+			assert_eq!(count_calls(&program, "detect --terse"), 1);
+			assert!(cache
+				.get_preferred_backend(Some("DP-2".to_string()), &backends)
+				.is_err());
+			wait_until(|| is_detected(&cache));
+			assert_eq!(count_calls(&program, "detect --terse"), 2);
+
+			fs::remove_dir_all(program.parent().unwrap()).unwrap();
+		});
+	}
+}
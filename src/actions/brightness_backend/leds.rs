@@ -0,0 +1,105 @@
+use anyhow::bail;
+use std::{
+	fs,
+	path::{Path, PathBuf},
+};
+
+use super::{
+	div_round_u32, read_sysfs_brightness, read_sysfs_u32, BrightnessBackend,
+	BrightnessBackendConstructor, LEDS_PATH,
+};
+
+/// Changes the brightness of LED class devices (/sys/class/leds/NAME), like
+/// keyboard backlights that aren't exposed by UPower
+pub(super) struct Leds {
+	name: String,
+	path: PathBuf,
+	max: u32,
+}
+
+impl BrightnessBackendConstructor for Leds {
+	fn try_new(device_name: Option<String>) -> anyhow::Result<Self> {
+		Self::new(Path::new(LEDS_PATH), device_name)
+	}
+}
+
+impl Leds {
+	/// Uses the LED device in the LED class directory (`root`)
+	fn new(root: &Path, device_name: Option<String>) -> anyhow::Result<Self> {
+		let Some(name) = device_name else {
+			bail!("No LED device name given");
+		};
+		// Don't allow reaching outside of the LED class directory
+		if name.contains('/') || name == "." || name == ".." {
+			bail!("Invalid LED device name '{}'", name);
+		}
+		let path = root.join(&name);
+		if !path.exists() {
+			bail!("LED device '{}' does not exist", name);
+		}
+		let max = read_sysfs_u32(&path.join("max_brightness"))?;
+
+		Ok(Self { name, path, max })
+	}
+}
+
+impl BrightnessBackend for Leds {
+	fn get_current(&mut self) -> u32 {
+		read_sysfs_brightness(&self.name, &self.path)
+	}
+
+	fn get_max(&mut self) -> u32 {
+		self.max
+	}
+
+	/// LEDs can have very few brightness levels, so always change by at least one
+	fn get_step(&mut self, by: u32) -> u32 {
+		div_round_u32(by * self.max, 100).max(1)
+	}
+
+	fn set_raw(&mut self, val: u32) -> anyhow::Result<()> {
+		let val = val.min(self.max);
+		Ok(fs::write(self.path.join("brightness"), val.to_string())?)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn changes_led_brightness() {
+		let root = std::env::temp_dir().join(format!("swayosd-leds-{}", std::process::id()));
+		let device = root.join("tpacpi::kbd_backlight");
+		fs::create_dir_all(&device).unwrap();
+		fs::write(device.join("max_brightness"), "2\n").unwrap();
+		fs::write(device.join("brightness"), "0\n").unwrap();
+
+		let mut leds = Leds::new(&root, Some("tpacpi::kbd_backlight".to_string())).unwrap();
+		assert_eq!(leds.get_max(), 2);
+		// Small steps still change the brightness by one level
+		leds.raise(5, 0).unwrap();
+		assert_eq!(leds.get_current(), 1);
+		leds.raise(100, 0).unwrap();
+		assert_eq!(leds.get_current(), 2);
+		leds.lower(5, 0).unwrap();
+		assert_eq!(leds.get_current(), 1);
+		leds.lower(5, 100).unwrap();
+		assert_eq!(leds.get_current(), 2);
+		leds.set(0, 0).unwrap();
+		assert_eq!(leds.get_current(), 0);
+		assert_eq!(leds.get_percent(), 0);
+
+		assert!(Leds::new(&root, None).is_err());
+		assert!(Leds::new(&root, Some("input3::capslock".to_string())).is_err());
+		// The device exists, but outside of the LED class directory
+		let class = device.join("class");
+		fs::create_dir_all(&class).unwrap();
+		for name in ["..", "../../tpacpi::kbd_backlight"] {
+			assert!(Leds::new(&class, Some(name.to_string())).is_err());
+		}
+		assert!(Leds::new(&device, Some(".".to_string())).is_err());
+
+		fs::remove_dir_all(root).unwrap();
+	}
+}
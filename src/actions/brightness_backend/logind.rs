@@ -0,0 +1,181 @@
+use anyhow::Context;
+use std::path::{Path, PathBuf};
+use zbus::{blocking::Connection, proxy};
+
+use super::{
+	find_backlight_device, read_sysfs_brightness, read_sysfs_u32, BrightnessBackend,
+	BrightnessBackendConstructor, BACKLIGHT_PATH,
+};
+
+#[proxy(
+	default_service = "org.freedesktop.login1",
+	default_path = "/org/freedesktop/login1/session/auto",
+	interface = "org.freedesktop.login1.Session",
+	gen_async = false
+)]
+trait Session {
+	#[zbus(name = "SetBrightness")]
+	fn set_brightness(&self, subsystem: &str, name: &str, brightness: u32) -> zbus::Result<()>;
+
+	#[zbus(property)]
+	fn active(&self) -> zbus::Result<bool>;
+}
+
+/// Sets the brightness through the logind session, which doesn't require any
+/// write permissions. The brightness is read from sysfs.
+pub(super) struct Logind {
+	proxy: SessionProxy<'static>,
+	name: String,
+	path: PathBuf,
+	max: u32,
+}
+
+impl BrightnessBackendConstructor for Logind {
+	fn try_new(device_name: Option<String>) -> anyhow::Result<Self> {
+		Self::new(
+			Connection::system()?,
+			Path::new(BACKLIGHT_PATH),
+			device_name,
+		)
+	}
+}
+
+impl Logind {
+	/// Uses the backlight device in the backlight class directory (`root`),
+	/// and the session on the connection
+	fn new(
+		connection: Connection,
+		root: &Path,
+		device_name: Option<String>,
+	) -> anyhow::Result<Self> {
+		let path = find_backlight_device(root, device_name)?;
+		let name = path
+			.file_name()
+			.and_then(|name| name.to_str())
+			.context("Invalid backlight device name")?
+			.to_string();
+		let max = read_sysfs_u32(&path.join("max_brightness"))?;
+
+		let proxy = SessionProxy::new(&connection)?;
+		// Make sure that we're running inside of a session
+		proxy.active()?;
+
+		Ok(Self {
+			proxy,
+			name,
+			path,
+			max,
+		})
+	}
+}
+
+impl BrightnessBackend for Logind {
+	fn get_current(&mut self) -> u32 {
+		read_sysfs_brightness(&self.name, &self.path)
+	}
+
+	fn get_max(&mut self) -> u32 {
+		self.max
+	}
+
+	fn set_raw(&mut self, val: u32) -> anyhow::Result<()> {
+		let val = val.min(self.max);
+		Ok(self.proxy.set_brightness("backlight", &self.name, val)?)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_utils::mock_connection;
+	use std::fs;
+	use zbus::interface;
+
+	const SESSION_PATH: &str = "/org/freedesktop/login1/session/auto";
+
+	/// Writes the brightness to the fake sysfs directory, like logind does
+	struct MockSession {
+		root: PathBuf,
+	}
+
+	#[interface(name = "org.freedesktop.login1.Session")]
+	impl MockSession {
+		#[zbus(name = "SetBrightness")]
+		fn set_brightness(
+			&self,
+			subsystem: &str,
+			name: &str,
+			brightness: u32,
+		) -> zbus::fdo::Result<()> {
+			assert_eq!(subsystem, "backlight");
+			fs::write(
+				self.root.join(name).join("brightness"),
+				brightness.to_string(),
+			)
+			.map_err(|e| zbus::fdo::Error::IOError(e.to_string()))
+		}
+
+		#[zbus(property)]
+		fn active(&self) -> bool {
+			true
+		}
+	}
+
+	/// Creates a backlight class directory with a single backlight device
+	fn fake_sysfs(test_name: &str) -> PathBuf {
+		let root = std::env::temp_dir().join(format!(
+			"swayosd-logind-{}-{}",
+			test_name,
+			std::process::id()
+		));
+		let device = root.join("intel_backlight");
+		fs::create_dir_all(&device).unwrap();
+		fs::write(device.join("max_brightness"), "200\n").unwrap();
+		fs::write(device.join("brightness"), "100\n").unwrap();
+		root
+	}
+
+	#[test]
+	fn sets_brightness_through_session() {
+		let root = fake_sysfs("session");
+		let session = MockSession { root: root.clone() };
+		let (_server, client) = async_std::task::block_on(mock_connection(move |builder| {
+			builder.serve_at(SESSION_PATH, session)
+		}))
+		.unwrap();
+
+		let mut logind = Logind::new(client.into(), &root, None).unwrap();
+		assert_eq!(logind.name, "intel_backlight");
+		assert_eq!(logind.get_max(), 200);
+		assert_eq!(logind.get_current(), 100);
+		assert_eq!(logind.get_percent(), 50);
+
+		logind.raise(10, 0).unwrap();
+		assert_eq!(logind.get_current(), 120);
+		logind.lower(50, 5).unwrap();
+		assert_eq!(logind.get_current(), 20);
+		logind.set(1, 5).unwrap();
+		assert_eq!(logind.get_current(), 10);
+		logind.set_raw(500).unwrap();
+		assert_eq!(logind.get_current(), 200);
+
+		fs::remove_dir_all(root).unwrap();
+	}
+
+	#[test]
+	fn fails_without_session_or_device() {
+		let root = fake_sysfs("no-session");
+		let session = MockSession { root: root.clone() };
+		// Serve the session somewhere else, the connection doesn't reply
+		// without any objects
+		let (_server, client) = async_std::task::block_on(mock_connection(move |builder| {
+			builder.serve_at("/org/freedesktop/login1/session/other", session)
+		}))
+		.unwrap();
+		let connection = Connection::from(client);
+		assert!(Logind::new(connection.clone(), &root, None).is_err());
+		assert!(Logind::new(connection, &root, Some("acpi_video0".to_string())).is_err());
+
+		fs::remove_dir_all(root).unwrap();
+	}
+}
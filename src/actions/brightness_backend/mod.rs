@@ -1,9 +1,48 @@
-use self::{blight::Blight, brightnessctl::BrightnessCtl};
+use anyhow::{bail, Context};
+use async_channel::Sender;
+use nix::poll::{poll, PollFd, PollFlags};
+use std::{
+	collections::HashMap,
+	fs::{self, File},
+	io::{Read, Seek, SeekFrom},
+	os::fd::AsFd,
+	path::{Path, PathBuf},
+	thread,
+	time::Duration,
+};
+
+use self::{
+	blight::Blight,
+	brightnessctl::BrightnessCtl,
+	ddcutil::{DdcMonitors, DdcUtil, DDCUTIL_PROGRAM},
+	leds::Leds,
+	logind::Logind,
+};
+use crate::config::user::BrightnessBackendType;
 
 mod blight;
 
 mod brightnessctl;
 
+mod ddcutil;
+
+mod leds;
+
+mod logind;
+
+pub const BACKLIGHT_PATH: &str = "/sys/class/backlight";
+pub const DRM_PATH: &str = "/sys/class/drm";
+const LEDS_PATH: &str = "/sys/class/leds";
+
+/// How often to check the backlights for changes, when not notified by sysfs
+const WATCHER_POLL_TIMEOUT: u16 = 500;
+
+/// The device name that changes the brightness of all devices
+pub const ALL_DEVICES: &str = "all";
+
+/// The device name prefix of LED class devices (ex: "leds:tpacpi::kbd_backlight")
+pub const LEDS_PREFIX: &str = "leds:";
+
 pub type BrightnessBackendResult = anyhow::Result<Box<dyn BrightnessBackend>>;
 
 pub trait BrightnessBackendConstructor: BrightnessBackend + Sized + 'static {
@@ -23,16 +62,347 @@ pub trait BrightnessBackend {
 	fn get_current(&mut self) -> u32;
 	fn get_max(&mut self) -> u32;
 
-	fn lower(&mut self, by: u32, min: u32) -> anyhow::Result<()>;
-	fn raise(&mut self, by: u32, min: u32) -> anyhow::Result<()>;
-	fn set(&mut self, val: u32, min: u32) -> anyhow::Result<()>;
+	fn get_percent(&mut self) -> u32 {
+		match self.get_max() {
+			0 => 0,
+			max => div_round_u32(self.get_current() * 100, max),
+		}
+	}
+
+	/// Returns the raw step for the percentage
+	fn get_step(&mut self, by: u32) -> u32 {
+		div_round_u32(by * self.get_max(), 100)
+	}
+
+	fn lower(&mut self, by: u32, min: u32) -> anyhow::Result<()> {
+		let curr = self.get_current();
+		let min_raw = div_round_u32(min * self.get_max(), 100);
+		let step = self.get_step(by);
+		self.set_raw(curr.saturating_sub(step).max(min_raw))
+	}
+
+	fn raise(&mut self, by: u32, min: u32) -> anyhow::Result<()> {
+		let curr = self.get_current();
+		let min_raw = div_round_u32(min * self.get_max(), 100);
+		let step = self.get_step(by);
+		self.set_raw((curr + step).max(min_raw))
+	}
+
+	fn set(&mut self, val: u32, min: u32) -> anyhow::Result<()> {
+		let max = self.get_max();
+		self.set_raw(div_round_u32(val.clamp(min, 100) * max, 100))
+	}
+
+	/// Sets the brightness to the raw value, clamped to the max
+	fn set_raw(&mut self, val: u32) -> anyhow::Result<()>;
+
+	/// Whether changing the brightness is cheap enough for each step of a
+	/// fade. Backends that spawn a process for each change should return false
+	fn supports_fading(&self) -> bool {
+		true
+	}
+}
+
+/// Remembers which backend works for each device, so that the backends
+/// before it aren't tried (and their errors logged) again on every change.
+/// Also remembers the detected DDC/CI monitors, since detecting them is slow
+pub struct BrightnessBackendCache {
+	/// The working backend by device name and the preferred backends
+	resolved: HashMap<(Option<String>, Vec<BrightnessBackendType>), BrightnessBackendType>,
+	ddc_monitors: DdcMonitors,
+}
+
+impl Default for BrightnessBackendCache {
+	fn default() -> Self {
+		Self {
+			resolved: HashMap::new(),
+			ddc_monitors: DdcMonitors::new(Path::new(DDCUTIL_PROGRAM)),
+		}
+	}
+}
+
+impl BrightnessBackendCache {
+	fn try_backend(
+		&mut self,
+		backend_type: BrightnessBackendType,
+		device_name: Option<String>,
+	) -> BrightnessBackendResult {
+		match backend_type {
+			BrightnessBackendType::BrightnessCtl => BrightnessCtl::try_new_boxed(device_name),
+			BrightnessBackendType::Sysfs => Blight::try_new_boxed(device_name),
+			BrightnessBackendType::Logind => Logind::try_new_boxed(device_name),
+			BrightnessBackendType::DdcUtil => {
+				let monitor = self.ddc_monitors.find(device_name.as_deref())?;
+				let backend = DdcUtil::new(&monitor);
+				if backend.is_err() {
+					// The monitor might be on another bus now
+					self.ddc_monitors.detect();
+				}
+				Ok(Box::new(backend?))
+			}
+		}
+	}
+
+	/// Detects the DDC/CI monitors in the background, so that they can be
+	/// used right away later
+	pub fn detect_ddc_monitors(&self) {
+		self.ddc_monitors.detect();
+	}
+
+	/// Returns the backends of all detected monitors that support DDC/CI, by connector
+	pub fn get_ddc_backends(&mut self) -> Vec<(String, BrightnessBackendResult)> {
+		let monitors = match self.ddc_monitors.get() {
+			Ok(monitors) => monitors,
+			Err(e) => {
+				eprintln!("Could not use the DDC/CI monitors: {}", e);
+				return Vec::new();
+			}
+		};
+		monitors
+			.into_iter()
+			.map(|(name, monitor)| {
+				let backend = DdcUtil::new(&monitor);
+				(name, backend.map(|backend| Box::new(backend) as _))
+			})
+			.collect()
+	}
+
+	/// Tries each backend in the order of preference, and uses the first one
+	/// that works. The backend that worked is used directly the next time
+	pub fn get_preferred_backend(
+		&mut self,
+		device_name: Option<String>,
+		preferred: &[BrightnessBackendType],
+	) -> BrightnessBackendResult {
+		// LED class devices are only supported by their own backend
+		if let Some(name) = device_name
+			.as_deref()
+			.and_then(|name| name.strip_prefix(LEDS_PREFIX))
+		{
+			return Leds::try_new_boxed(Some(name.to_string()));
+		}
+
+		let key = (device_name.clone(), preferred.to_vec());
+		if let Some(backend_type) = self.resolved.get(&key).copied() {
+			match self.try_backend(backend_type, device_name.clone()) {
+				Ok(backend) => return Ok(backend),
+				Err(e) => {
+					eprintln!(
+						"The {:?} brightness backend stopped working: {}",
+						backend_type, e
+					);
+					self.resolved.remove(&key);
+				}
+			}
+		}
+
+		let mut errors = Vec::new();
+		for backend_type in preferred {
+			match self.try_backend(*backend_type, device_name.clone()) {
+				Ok(backend) => {
+					println!(
+						"Using the {:?} brightness backend for {}",
+						backend_type,
+						device_name.as_deref().unwrap_or("the default device")
+					);
+					self.resolved.insert(key, *backend_type);
+					return Ok(backend);
+				}
+				Err(e) => {
+					eprintln!("The {:?} brightness backend failed: {}", backend_type, e);
+					errors.push(format!("{:?}", backend_type));
+				}
+			}
+		}
+		bail!(
+			"No brightness backend available, tried: {}",
+			errors.join(", ")
+		)
+	}
+}
+
+/// Returns the sysfs path of the backlight device in the backlight class
+/// directory, or the first one if no name is given
+pub(super) fn find_backlight_device(
+	root: &Path,
+	device_name: Option<String>,
+) -> anyhow::Result<PathBuf> {
+	if let Some(name) = device_name {
+		let path = root.join(&name);
+		if !path.exists() {
+			bail!("Backlight device '{}' does not exist", name);
+		}
+		return Ok(path);
+	}
+
+	let mut paths: Vec<PathBuf> = fs::read_dir(root)?
+		.filter_map(|entry| entry.ok().map(|entry| entry.path()))
+		.collect();
+	paths.sort();
+	paths
+		.into_iter()
+		.next()
+		.context("No backlight device found")
 }
 
-#[allow(dead_code)]
-pub fn get_preferred_backend(device_name: Option<String>) -> BrightnessBackendResult {
-	println!("Trying BrightnessCtl Backend...");
-	BrightnessCtl::try_new_boxed(device_name.clone()).or_else(|_| {
-		println!("...Command failed! Falling back to Blight");
-		Blight::try_new_boxed(device_name)
+/// Returns the names of all backlight devices in the backlight class directory (`root`)
+pub fn list_backlight_devices(root: &Path) -> Vec<String> {
+	let mut names: Vec<String> = fs::read_dir(root)
+		.into_iter()
+		.flatten()
+		.filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+		.collect();
+	names.sort();
+	names
+}
+
+/// Returns the name of the backlight device that belongs to the monitor
+/// connector (ex: "eDP-1"). The backlight is a child of the DRM connector in
+/// the DRM class directory (`drm_root`)
+pub fn find_monitor_backlight(
+	drm_root: &Path,
+	backlight_root: &Path,
+	connector: &str,
+) -> Option<String> {
+	let backlights = list_backlight_devices(backlight_root);
+
+	for entry in fs::read_dir(drm_root).ok()?.flatten() {
+		// Ex: "card0-eDP-1"
+		let name = entry.file_name();
+		let is_connector = name
+			.to_str()
+			.and_then(|name| name.split_once('-'))
+			.is_some_and(|(_, name)| name == connector);
+		if !is_connector {
+			continue;
+		}
+		if let Some(backlight) = backlights
+			.iter()
+			.find(|backlight| entry.path().join(backlight).exists())
+		{
+			return Some(backlight.clone());
+		}
+	}
+	None
+}
+
+/// Spawns a thread that sends the name of the backlight device each time its
+/// brightness changes. Waits for sysfs notifications, with polling as a fallback.
+pub fn spawn_backlight_watcher(sender: Sender<String>) {
+	thread::spawn(move || {
+		let mut devices: Vec<(String, File, Option<u32>)> =
+			list_backlight_devices(Path::new(BACKLIGHT_PATH))
+				.into_iter()
+				.filter_map(|name| {
+					let path = Path::new(BACKLIGHT_PATH)
+						.join(&name)
+						.join("actual_brightness");
+					match File::open(&path) {
+						Ok(file) => Some((name, file, None)),
+						Err(e) => {
+							eprintln!("Could not watch {}: {}", path.display(), e);
+							None
+						}
+					}
+				})
+				.collect();
+		if devices.is_empty() {
+			eprintln!("No backlight devices to watch");
+			return;
+		}
+
+		loop {
+			for (name, file, last) in devices.iter_mut() {
+				let Ok(value) = read_from_start(file) else {
+					continue;
+				};
+				if last.is_some_and(|last| last != value)
+					&& sender.send_blocking(name.clone()).is_err()
+				{
+					return;
+				}
+				*last = Some(value);
+			}
+
+			let mut fds: Vec<PollFd> = devices
+				.iter()
+				.map(|(_, file, _)| {
+					PollFd::new(file.as_fd(), PollFlags::POLLPRI | PollFlags::POLLERR)
+				})
+				.collect();
+			if let Err(e) = poll(&mut fds, WATCHER_POLL_TIMEOUT) {
+				eprintln!("Backlight watcher poll error: {}", e);
+				thread::sleep(Duration::from_millis(WATCHER_POLL_TIMEOUT.into()));
+			}
+		}
+	});
+}
+
+/// Sysfs attributes need to be re-read from the start to get the new value
+fn read_from_start(file: &mut File) -> anyhow::Result<u32> {
+	file.seek(SeekFrom::Start(0))?;
+	let mut value = String::new();
+	file.read_to_string(&mut value)?;
+	Ok(value.trim().parse()?)
+}
+
+pub(super) fn read_sysfs_u32(path: &Path) -> anyhow::Result<u32> {
+	let value = fs::read_to_string(path)?;
+	value
+		.trim()
+		.parse()
+		.with_context(|| format!("Invalid value in {}", path.display()))
+}
+
+/// Reads the brightness of the sysfs device, or logs the error and returns 0
+pub(super) fn read_sysfs_brightness(name: &str, path: &Path) -> u32 {
+	read_sysfs_u32(&path.join("brightness")).unwrap_or_else(|e| {
+		eprintln!("Could not read the brightness of {}: {}", name, e);
+		0
 	})
 }
+
+pub(super) fn div_round_u32(a: u32, b: u32) -> u32 {
+	(a + b / 2) / b
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn finds_monitor_backlight() {
+		let root = std::env::temp_dir().join(format!("swayosd-drm-{}", std::process::id()));
+		let (drm, backlight) = (root.join("drm"), root.join("backlight"));
+		for dir in [
+			drm.join("card0-eDP-1/intel_backlight"),
+			drm.join("card0-HDMI-A-1"),
+			drm.join("card1-eDP-2/amdgpu_bl1"),
+			backlight.join("intel_backlight"),
+			backlight.join("amdgpu_bl1"),
+			backlight.join("acpi_video0"),
+		] {
+			fs::create_dir_all(dir).unwrap();
+		}
+
+		assert_eq!(
+			find_monitor_backlight(&drm, &backlight, "eDP-1").as_deref(),
+			Some("intel_backlight")
+		);
+		assert_eq!(
+			find_monitor_backlight(&drm, &backlight, "eDP-2").as_deref(),
+			Some("amdgpu_bl1")
+		);
+		// External monitors don't have a backlight device
+		assert_eq!(find_monitor_backlight(&drm, &backlight, "HDMI-A-1"), None);
+		assert_eq!(find_monitor_backlight(&drm, &backlight, "DP-3"), None);
+		// Only the whole connector name matches
+		assert_eq!(find_monitor_backlight(&drm, &backlight, "eDP"), None);
+		assert_eq!(
+			list_backlight_devices(&backlight),
+			["acpi_video0", "amdgpu_bl1", "intel_backlight"]
+		);
+
+		fs::remove_dir_all(root).unwrap();
+	}
+}
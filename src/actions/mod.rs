@@ -1,3 +1,3 @@
 pub(crate) mod brightness_backend;
 pub(crate) mod mpris;
-pub(crate) mod pulse;
+pub(crate) mod volume_backend;
@@ -0,0 +1,385 @@
+use pulse::{channelmap, volume::ChannelVolumes};
+use std::{
+	path::Path,
+	process::Command,
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		Arc,
+	},
+	thread,
+	time::Duration,
+};
+
+use self::{pulseaudio::VolumeController, wpctl::WpCtl};
+use crate::config::user::VolumeBackendType;
+
+pub mod pulseaudio;
+
+mod wpctl;
+
+/// How many times to try reconnecting to the volume backend in the background
+const RECONNECT_ATTEMPTS: u32 = 5;
+
+/// The delay before the first background reconnection attempt, doubled after
+/// each failed attempt
+const RECONNECT_BACKOFF: Duration = Duration::from_millis(50);
+
+/// Whether we're operating on an output (sink) or input (source) device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DeviceKind {
+	Sink,
+	Source,
+}
+
+/// Minimal device info needed by SwayOSD.
+#[derive(Debug, Clone)]
+pub struct DeviceInfo {
+	pub kind: DeviceKind,
+	pub index: u32,
+	pub name: String,
+	pub volume: ChannelVolumes,
+	pub channel_map: channelmap::Map,
+	pub mute: bool,
+	/// The battery percentage of Bluetooth devices, if the backend knows it
+	pub battery: Option<u8>,
+	/// The address of Bluetooth devices, used for looking up the battery through BlueZ
+	pub bluetooth_address: Option<String>,
+}
+
+/// Parses the battery level of Bluetooth devices. Ex: "bluetooth.battery" = "80%"
+fn parse_battery(value: &str) -> Option<u8> {
+	value.trim().trim_end_matches('%').parse::<u8>().ok()
+}
+
+pub type VolumeBackendResult = anyhow::Result<Box<dyn VolumeBackend>>;
+
+pub trait VolumeBackendConstructor: VolumeBackend + Sized + 'static {
+	fn try_new() -> anyhow::Result<Self>;
+
+	fn try_new_boxed() -> VolumeBackendResult {
+		let backend = Self::try_new();
+		match backend {
+			Ok(backend) => Ok(Box::new(backend)),
+			Err(e) => Err(e),
+		}
+	}
+}
+
+pub trait VolumeBackend {
+	/// Backends that keep a connection open should return false when it's
+	/// lost, so that the backend gets recreated.
+	fn is_connected(&mut self) -> bool {
+		true
+	}
+
+	fn get_default_device(&mut self, kind: DeviceKind) -> anyhow::Result<DeviceInfo>;
+	fn get_device_by_name(&mut self, kind: DeviceKind, name: &str) -> anyhow::Result<DeviceInfo>;
+	fn get_device_by_index(&mut self, kind: DeviceKind, index: u32) -> anyhow::Result<DeviceInfo>;
+
+	fn set_volume_by_index(
+		&mut self,
+		kind: DeviceKind,
+		index: u32,
+		volume: &ChannelVolumes,
+	) -> anyhow::Result<()>;
+	fn set_mute_by_index(&mut self, kind: DeviceKind, index: u32, mute: bool)
+		-> anyhow::Result<()>;
+
+	/// Plays the sound file on the sink without blocking. Skipped while the
+	/// previous sound is still playing
+	fn play_sound_by_index(&mut self, index: u32, path: &Path) -> anyhow::Result<()>;
+}
+
+/// Plays sound files through an external player command, one at a time
+#[derive(Default)]
+struct SoundPlayer {
+	playing: Arc<AtomicBool>,
+}
+
+impl SoundPlayer {
+	/// Spawns the player command without blocking and returns true, or
+	/// returns false while the previous sound is still playing, so that the
+	/// sounds don't stack up when holding down the volume key
+	fn play(&self, mut command: Command) -> anyhow::Result<bool> {
+		if self.playing.swap(true, Ordering::AcqRel) {
+			return Ok(false);
+		}
+		let mut child = match command.spawn() {
+			Ok(child) => child,
+			Err(e) => {
+				self.playing.store(false, Ordering::Release);
+				return Err(e.into());
+			}
+		};
+		let playing = self.playing.clone();
+		thread::spawn(move || {
+			let _ = child.wait();
+			playing.store(false, Ordering::Release);
+		});
+		Ok(true)
+	}
+}
+
+fn try_backend(backend_type: VolumeBackendType) -> VolumeBackendResult {
+	match backend_type {
+		VolumeBackendType::PulseAudio => {
+			println!("Trying PulseAudio Backend...");
+			VolumeController::try_new_boxed()
+		}
+		VolumeBackendType::PipeWire => {
+			println!("Trying PipeWire (wpctl) Backend...");
+			WpCtl::try_new_boxed()
+		}
+	}
+}
+
+pub fn get_preferred_backend(preferred: Option<VolumeBackendType>) -> VolumeBackendResult {
+	let preferred = preferred.unwrap_or(VolumeBackendType::PulseAudio);
+	try_backend(preferred).or_else(|error| {
+		let fallback = match preferred {
+			VolumeBackendType::PulseAudio => VolumeBackendType::PipeWire,
+			VolumeBackendType::PipeWire => VolumeBackendType::PulseAudio,
+		};
+		println!(
+			"...Failed with error: {}! Falling back to {:?}",
+			error, fallback
+		);
+		try_backend(fallback)
+	})
+}
+
+/// Keeps the volume backend connected. The backend gets recreated when its
+/// connection got lost (ex: when pipewire-pulse restarts), and failed
+/// connections are retried with an exponential backoff.
+/// Note: The retries aren't timed here, the caller runs `retry` after the
+/// `retry_delay`, ex: on a main loop timer
+pub struct VolumeConnection {
+	backend: Option<Box<dyn VolumeBackend>>,
+	connect: Box<dyn Fn() -> VolumeBackendResult>,
+	/// The next background reconnection attempt, if one is pending
+	retry_attempt: Option<u32>,
+}
+
+impl VolumeConnection {
+	pub fn new(preferred: Option<VolumeBackendType>) -> Self {
+		Self::with_connect(Box::new(move || get_preferred_backend(preferred)))
+	}
+
+	fn with_connect(connect: Box<dyn Fn() -> VolumeBackendResult>) -> Self {
+		Self {
+			backend: None,
+			connect,
+			retry_attempt: None,
+		}
+	}
+
+	/// Returns the backend, connecting to it if needed. Only tries once to
+	/// not block the caller, and starts the background retries on failure
+	pub fn get(&mut self) -> anyhow::Result<&mut (dyn VolumeBackend + 'static)> {
+		if self
+			.backend
+			.as_mut()
+			.is_some_and(|backend| !backend.is_connected())
+		{
+			eprintln!("Reconnecting to the volume backend...");
+			self.backend.take();
+		}
+		if self.backend.is_none() {
+			match (self.connect)() {
+				Ok(backend) => {
+					self.backend.replace(backend);
+					self.retry_attempt.take();
+					println!("Connected to the volume backend");
+				}
+				Err(e) => {
+					self.retry_attempt.get_or_insert(1);
+					return Err(e);
+				}
+			}
+		}
+		Ok(self.backend.as_deref_mut().unwrap())
+	}
+
+	/// Returns the delay before the pending background reconnection attempt
+	pub fn retry_delay(&self) -> Option<Duration> {
+		self.retry_attempt
+			.map(|attempt| RECONNECT_BACKOFF * 2_u32.pow(attempt - 1))
+	}
+
+	/// Runs the pending background reconnection attempt. Schedules the next
+	/// attempt on failure, until giving up after `RECONNECT_ATTEMPTS`
+	pub fn retry(&mut self) {
+		let Some(attempt) = self.retry_attempt.take() else {
+			return;
+		};
+		if self.backend.is_some() {
+			return;
+		}
+		match (self.connect)() {
+			Ok(backend) => {
+				self.backend.replace(backend);
+				println!("Reconnected to the volume backend");
+			}
+			Err(e) => {
+				eprintln!(
+					"Volume backend reconnection attempt {}/{} failed: {}",
+					attempt, RECONNECT_ATTEMPTS, e
+				);
+				if attempt < RECONNECT_ATTEMPTS {
+					self.retry_attempt = Some(attempt + 1);
+				} else {
+					eprintln!("Could not reconnect to the volume backend, giving up");
+				}
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::{cell::Cell, rc::Rc, time::Instant};
+
+	/// A backend without devices, which can lose its connection
+	struct FakeBackend {
+		connected: Rc<Cell<bool>>,
+	}
+
+	impl VolumeBackend for FakeBackend {
+		fn is_connected(&mut self) -> bool {
+			self.connected.get()
+		}
+
+		fn get_default_device(&mut self, _: DeviceKind) -> anyhow::Result<DeviceInfo> {
+			anyhow::bail!("No devices")
+		}
+
+		fn get_device_by_name(&mut self, _: DeviceKind, _: &str) -> anyhow::Result<DeviceInfo> {
+			anyhow::bail!("No devices")
+		}
+
+		fn get_device_by_index(&mut self, _: DeviceKind, _: u32) -> anyhow::Result<DeviceInfo> {
+			anyhow::bail!("No devices")
+		}
+
+		fn set_volume_by_index(
+			&mut self,
+			_: DeviceKind,
+			_: u32,
+			_: &ChannelVolumes,
+		) -> anyhow::Result<()> {
+			anyhow::bail!("No devices")
+		}
+
+		fn set_mute_by_index(&mut self, _: DeviceKind, _: u32, _: bool) -> anyhow::Result<()> {
+			anyhow::bail!("No devices")
+		}
+
+		fn play_sound_by_index(&mut self, _: u32, _: &Path) -> anyhow::Result<()> {
+			Ok(())
+		}
+	}
+
+	/// The fake server, which the connection connects to while it's running
+	#[derive(Clone, Default)]
+	struct FakeServer {
+		running: Rc<Cell<bool>>,
+		/// Whether the backend is still connected
+		connected: Rc<Cell<bool>>,
+		connections: Rc<Cell<u32>>,
+	}
+
+	impl FakeServer {
+		fn connection(&self) -> VolumeConnection {
+			let server = self.clone();
+			VolumeConnection::with_connect(Box::new(move || {
+				server.connections.set(server.connections.get() + 1);
+				if !server.running.get() {
+					anyhow::bail!("Connection refused");
+				}
+				server.connected.set(true);
+				Ok(Box::new(FakeBackend {
+					connected: server.connected.clone(),
+				}))
+			}))
+		}
+	}
+
+	#[test]
+	fn reconnects_lost_backend() {
+		let server = FakeServer::default();
+		server.running.set(true);
+		let mut connection = server.connection();
+
+		connection.get().unwrap();
+		connection.get().unwrap();
+		assert_eq!(server.connections.get(), 1);
+
+		// Ex: the server restarted
+		server.connected.set(false);
+		connection.get().unwrap();
+		assert_eq!(server.connections.get(), 2);
+		assert_eq!(connection.retry_delay(), None);
+	}
+
+	#[test]
+	fn retries_with_backoff() {
+		let server = FakeServer::default();
+		let mut connection = server.connection();
+
+		assert!(connection.get().is_err());
+		let mut delays = Vec::new();
+		while let Some(delay) = connection.retry_delay() {
+			delays.push(delay.as_millis());
+			connection.retry();
+		}
+		assert_eq!(delays, [50, 100, 200, 400, 800]);
+		assert_eq!(server.connections.get(), 1 + RECONNECT_ATTEMPTS);
+
+		// Failing again starts new retries, which stop once connected
+		assert!(connection.get().is_err());
+		assert_eq!(connection.retry_delay(), Some(RECONNECT_BACKOFF));
+		connection.retry();
+		server.running.set(true);
+		connection.retry();
+		assert_eq!(connection.retry_delay(), None);
+		connection.get().unwrap();
+		assert_eq!(server.connections.get(), 4 + RECONNECT_ATTEMPTS);
+
+		// A pending retry does nothing after connecting in the meantime
+		server.connected.set(false);
+		server.running.set(false);
+		assert!(connection.get().is_err());
+		server.running.set(true);
+		connection.get().unwrap();
+		connection.retry();
+		assert_eq!(connection.retry_delay(), None);
+		assert_eq!(server.connections.get(), 6 + RECONNECT_ATTEMPTS);
+	}
+
+	#[test]
+	fn sound_player_skips_while_playing() {
+		let sleep = || {
+			let mut command = Command::new("sleep");
+			command.arg("0.2");
+			command
+		};
+		let player = SoundPlayer::default();
+		assert!(player.play(sleep()).unwrap());
+		assert!(!player.play(sleep()).unwrap());
+
+		let start = Instant::now();
+		while player.playing.load(Ordering::Acquire) {
+			assert!(start.elapsed() < Duration::from_secs(5));
+			thread::sleep(Duration::from_millis(20));
+		}
+		assert!(player.play(sleep()).unwrap());
+	}
+
+	#[test]
+	fn sound_player_recovers_from_spawn_errors() {
+		let player = SoundPlayer::default();
+		assert!(player.play(Command::new("/nonexistent/player")).is_err());
+		assert!(!player.playing.load(Ordering::Acquire));
+	}
+}
@@ -0,0 +1,715 @@
+use async_channel::Sender;
+use gtk::glib::clone;
+use pulse::{
+	callbacks::ListResult,
+	context::{introspect, subscribe::InterestMaskSet, Context},
+	mainloop::standard::{IterateResult, Mainloop},
+	operation::{Operation, State},
+	proplist::Proplist,
+	volume::ChannelVolumes,
+};
+
+use std::cell::{Cell, RefCell};
+use std::ops::Deref;
+use std::path::Path;
+use std::process::Command;
+use std::rc::Rc;
+use std::thread::{self, sleep};
+use std::time::Duration;
+
+use super::{
+	parse_battery, DeviceInfo, DeviceKind, SoundPlayer, VolumeBackend, VolumeBackendConstructor,
+};
+
+/// How many times to try connecting before giving up.
+const CONNECT_ATTEMPTS: u32 = 5;
+/// The delay before the first retry, doubled after each failed attempt.
+const CONNECT_BACKOFF: Duration = Duration::from_millis(50);
+/// The delay before restarting a listener after losing the connection.
+const LISTENER_RESTART_DELAY: Duration = Duration::from_secs(1);
+
+/// Error types for PulseAudio operations.
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum PulseError {
+	#[error("PulseAudio connect error: {0}")]
+	Connect(String),
+	#[error("PulseAudio operation error: {0}")]
+	Operation(String),
+	#[error("PulseAudio get info error: {0}")]
+	GetInfo(String),
+}
+
+impl From<pulse::error::PAErr> for PulseError {
+	fn from(error: pulse::error::PAErr) -> Self {
+		Self::Operation(
+			error
+				.to_string()
+				.unwrap_or_else(|| "Unknown PA error".to_string()),
+		)
+	}
+}
+
+impl From<&introspect::SinkInfo<'_>> for DeviceInfo {
+	fn from(info: &introspect::SinkInfo) -> Self {
+		let (battery, bluetooth_address) = get_bluetooth_info(&info.proplist);
+		DeviceInfo {
+			kind: DeviceKind::Sink,
+			index: info.index,
+			name: info.name.as_deref().unwrap_or_default().to_string(),
+			volume: info.volume,
+			channel_map: info.channel_map,
+			mute: info.mute,
+			battery,
+			bluetooth_address,
+		}
+	}
+}
+
+impl From<&introspect::SourceInfo<'_>> for DeviceInfo {
+	fn from(info: &introspect::SourceInfo) -> Self {
+		let (battery, bluetooth_address) = get_bluetooth_info(&info.proplist);
+		DeviceInfo {
+			kind: DeviceKind::Source,
+			index: info.index,
+			name: info.name.as_deref().unwrap_or_default().to_string(),
+			volume: info.volume,
+			channel_map: info.channel_map,
+			mute: info.mute,
+			battery,
+			bluetooth_address,
+		}
+	}
+}
+
+/// Returns the battery level and address of Bluetooth devices.
+/// PulseAudio stores the address in "device.string" while PipeWire uses "api.bluez5.address"
+fn get_bluetooth_info(proplist: &Proplist) -> (Option<u8>, Option<String>) {
+	if proplist
+		.get_str(pulse::proplist::properties::DEVICE_BUS)
+		.as_deref()
+		!= Some("bluetooth")
+	{
+		return (None, None);
+	}
+	let battery = proplist
+		.get_str("bluetooth.battery")
+		.and_then(|value| parse_battery(&value));
+	let address = proplist
+		.get_str("api.bluez5.address")
+		.or_else(|| proplist.get_str(pulse::proplist::properties::DEVICE_STRING));
+	(battery, address)
+}
+
+/// A source output, as needed for finding the applications that are recording
+struct RecordingStream {
+	source: u32,
+	corked: bool,
+	name: String,
+}
+
+/// Returns the unique names of the streams that are recording, ignoring the
+/// ones that record from a sink monitor (`monitors` are the source indices)
+fn get_recording_names(streams: &[RecordingStream], monitors: &[u32]) -> Vec<String> {
+	let mut names: Vec<String> = Vec::new();
+	for stream in streams {
+		if stream.corked || monitors.contains(&stream.source) || names.contains(&stream.name) {
+			continue;
+		}
+		names.push(stream.name.clone());
+	}
+	names
+}
+
+// ---------------------------------------------------------------------------
+// Callback helper: collects the first item from a PulseAudio list callback.
+//
+// libpulse-binding's SinkInfo/SourceInfo carry a lifetime parameter that
+// makes a generic function impractical (HRTB bounds don't unify). A macro
+// avoids the issue by expanding at each call site.
+// ---------------------------------------------------------------------------
+
+/// Fires a PulseAudio introspect query that yields `ListResult<&$info_ty>`,
+/// collects the first item as a `DeviceInfo`, and returns it (or an error).
+macro_rules! query_device {
+	($self:expr, $kind:expr, $introspect_method:ident ( $($arg:expr),* ), $info_ty:ty) => {{
+		let result: Rc<RefCell<Option<DeviceInfo>>> = Rc::new(RefCell::new(None));
+		let op = $self.introspect.$introspect_method(
+			$($arg,)*
+			clone!(
+				#[strong]
+				result,
+				move |list: ListResult<&$info_ty>| {
+					if let ListResult::Item(item) = list {
+						result.replace(Some(DeviceInfo::from(item)));
+					}
+				}
+			),
+		);
+		$self.wait_for_operation(op)?;
+		result
+			.take()
+			.ok_or_else(|| PulseError::GetInfo(format!("{:?} not found", $kind)))
+	}};
+}
+
+// ---------------------------------------------------------------------------
+// VolumeController: PulseAudio connection wrapper for both sinks and sources
+// ---------------------------------------------------------------------------
+
+pub struct VolumeController {
+	mainloop: Rc<RefCell<Mainloop>>,
+	context: Rc<RefCell<Context>>,
+	introspect: introspect::Introspector,
+	/// The server that was connected to, when it isn't the default one
+	server: Option<String>,
+	sound_player: SoundPlayer,
+}
+
+impl VolumeController {
+	pub fn create() -> Result<Self, PulseError> {
+		Self::connect(None)
+	}
+
+	/// Connects to the given server, or to the default one when None
+	fn connect(server: Option<&str>) -> Result<Self, PulseError> {
+		let mut proplist = Proplist::new()
+			.ok_or_else(|| PulseError::Connect("Failed to create proplist".into()))?;
+		proplist
+			.set_str(pulse::proplist::properties::APPLICATION_NAME, "SwayOSD")
+			.map_err(|_| PulseError::Connect("Failed to set application name".into()))?;
+
+		let mainloop = Mainloop::new()
+			.ok_or_else(|| PulseError::Connect("Failed to create mainloop".into()))?;
+		let mainloop = Rc::new(RefCell::new(mainloop));
+
+		let context = Context::new_with_proplist(mainloop.borrow().deref(), "SwayOSD", &proplist)
+			.ok_or_else(|| PulseError::Connect("Failed to create context".into()))?;
+		let context = Rc::new(RefCell::new(context));
+
+		// return Err(PulseError::Connect("Failed to connect context".into()));
+
+		context
+			.borrow_mut()
+			.connect(server, pulse::context::FlagSet::NOFLAGS, None)
+			.map_err(|_| PulseError::Connect("Failed to connect context".into()))?;
+
+		loop {
+			match mainloop.borrow_mut().iterate(true) {
+				IterateResult::Err(e) => return Err(e.into()),
+				IterateResult::Quit(_) => {
+					return Err(PulseError::Connect("Mainloop quit unexpectedly".into()));
+				}
+				IterateResult::Success(_) => {}
+			}
+			match context.borrow().get_state() {
+				pulse::context::State::Ready => break,
+				pulse::context::State::Failed | pulse::context::State::Terminated => {
+					return Err(PulseError::Connect(
+						"Context state failed/terminated".into(),
+					));
+				}
+				_ => {}
+			}
+		}
+
+		let introspect = context.borrow_mut().introspect();
+		Ok(Self {
+			mainloop,
+			context,
+			introspect,
+			server: server.map(str::to_string),
+			sound_player: SoundPlayer::default(),
+		})
+	}
+
+	/// Tries to connect multiple times with an exponential backoff in between.
+	/// Useful when the PulseAudio server (or pipewire-pulse) is restarting.
+	/// Blocks the current thread, so only use it outside of the main thread.
+	pub fn create_with_backoff() -> Result<Self, PulseError> {
+		let mut delay = CONNECT_BACKOFF;
+		let mut attempt = 1;
+		loop {
+			match Self::create() {
+				Ok(ctrl) => return Ok(ctrl),
+				Err(e) if attempt < CONNECT_ATTEMPTS => {
+					eprintln!(
+						"PulseAudio connection attempt {}/{} failed: {}. Retrying in {:?}",
+						attempt, CONNECT_ATTEMPTS, e, delay
+					);
+					sleep(delay);
+					delay *= 2;
+					attempt += 1;
+				}
+				Err(e) => return Err(e),
+			}
+		}
+	}
+
+	/// Returns the current state of the connection context. Dispatches any
+	/// pending events first so that a dropped connection is noticed.
+	pub fn get_state(&self) -> pulse::context::State {
+		if let IterateResult::Err(_) | IterateResult::Quit(_) =
+			self.mainloop.borrow_mut().iterate(false)
+		{
+			return pulse::context::State::Failed;
+		}
+		self.context.borrow().get_state()
+	}
+
+	/// Blocks the current thread and calls `on_change` once initially, and then
+	/// each time one of the facilities in the `mask` changes. Stops when
+	/// `on_change` returns false or when the connection is lost.
+	pub fn listen(
+		&mut self,
+		mask: InterestMaskSet,
+		mut on_change: impl FnMut(&mut Self) -> bool,
+	) -> Result<(), PulseError> {
+		let changed = Rc::new(Cell::new(true));
+		self.context
+			.borrow_mut()
+			.set_subscribe_callback(Some(Box::new(clone!(
+				#[strong]
+				changed,
+				move |_, _, _| changed.set(true)
+			))));
+		let op = self.context.borrow_mut().subscribe(mask, |_| {});
+		self.wait_for_operation(op)?;
+
+		loop {
+			if changed.replace(false) && !on_change(self) {
+				return Ok(());
+			}
+			match self.mainloop.borrow_mut().iterate(true) {
+				IterateResult::Err(e) => return Err(e.into()),
+				IterateResult::Quit(_) => {
+					return Err(PulseError::Operation("Mainloop quit unexpectedly".into()));
+				}
+				IterateResult::Success(_) => {}
+			}
+			if self.context.borrow().get_state() != pulse::context::State::Ready {
+				return Err(PulseError::Connect("Connection lost".into()));
+			}
+		}
+	}
+
+	/// Returns the names of the applications that are currently recording
+	/// from any source, except for sink monitors
+	pub fn get_recording_applications(&self) -> Result<Vec<String>, PulseError> {
+		let monitors: Rc<RefCell<Vec<u32>>> = Rc::new(RefCell::new(Vec::new()));
+		let op = self.introspect.get_source_info_list(clone!(
+			#[strong]
+			monitors,
+			move |list| {
+				if let ListResult::Item(info) = list
+					&& info.monitor_of_sink.is_some()
+				{
+					monitors.borrow_mut().push(info.index);
+				}
+			}
+		));
+		self.wait_for_operation(op)?;
+		let monitors = monitors.take();
+
+		let streams: Rc<RefCell<Vec<RecordingStream>>> = Rc::new(RefCell::new(Vec::new()));
+		let op = self.introspect.get_source_output_info_list(clone!(
+			#[strong]
+			streams,
+			move |list| {
+				let ListResult::Item(info) = list else {
+					return;
+				};
+				let name = info
+					.proplist
+					.get_str(pulse::proplist::properties::APPLICATION_NAME)
+					.or_else(|| info.name.as_ref().map(|name| name.to_string()))
+					.unwrap_or_else(|| "Unknown".to_string());
+				streams.borrow_mut().push(RecordingStream {
+					source: info.source,
+					corked: info.corked,
+					name,
+				});
+			}
+		));
+		self.wait_for_operation(op)?;
+
+		Ok(get_recording_names(&streams.take(), &monitors))
+	}
+
+	fn wait_for_operation<G: ?Sized>(&self, op: Operation<G>) -> Result<(), PulseError> {
+		loop {
+			match self.mainloop.borrow_mut().iterate(true) {
+				IterateResult::Err(e) => return Err(e.into()),
+				IterateResult::Quit(_) => {
+					return Err(PulseError::Operation("Mainloop quit unexpectedly".into()));
+				}
+				IterateResult::Success(_) => {}
+			}
+			match op.get_state() {
+				State::Done => return Ok(()),
+				State::Running => {}
+				State::Cancelled => {
+					return Err(PulseError::Operation("Operation cancelled".into()));
+				}
+			}
+		}
+	}
+
+	fn get_default_device_name(&self, kind: DeviceKind) -> Result<String, PulseError> {
+		let name: Rc<RefCell<Option<String>>> = Rc::new(RefCell::new(None));
+
+		let op = self.introspect.get_server_info(clone!(
+			#[strong]
+			name,
+			move |info| {
+				let value = match kind {
+					DeviceKind::Sink => &info.default_sink_name,
+					DeviceKind::Source => &info.default_source_name,
+				};
+				if let Some(cow) = value.as_ref() {
+					name.replace(Some(cow.to_string()));
+				}
+			}
+		));
+		self.wait_for_operation(op)?;
+
+		name.take()
+			.ok_or_else(|| PulseError::GetInfo("No default device name".into()))
+	}
+}
+
+impl VolumeBackendConstructor for VolumeController {
+	fn try_new() -> anyhow::Result<Self> {
+		Ok(Self::create()?)
+	}
+}
+
+impl VolumeBackend for VolumeController {
+	fn is_connected(&mut self) -> bool {
+		match self.get_state() {
+			pulse::context::State::Ready => true,
+			state => {
+				eprintln!("PulseAudio connection lost ({:?})", state);
+				false
+			}
+		}
+	}
+
+	fn get_default_device(&mut self, kind: DeviceKind) -> anyhow::Result<DeviceInfo> {
+		let name = self.get_default_device_name(kind)?;
+		self.get_device_by_name(kind, &name)
+	}
+
+	fn get_device_by_name(&mut self, kind: DeviceKind, name: &str) -> anyhow::Result<DeviceInfo> {
+		let device = match kind {
+			DeviceKind::Sink => {
+				query_device!(
+					self,
+					kind,
+					get_sink_info_by_name(name),
+					introspect::SinkInfo
+				)
+			}
+			DeviceKind::Source => {
+				query_device!(
+					self,
+					kind,
+					get_source_info_by_name(name),
+					introspect::SourceInfo
+				)
+			}
+		};
+		Ok(device?)
+	}
+
+	fn get_device_by_index(&mut self, kind: DeviceKind, index: u32) -> anyhow::Result<DeviceInfo> {
+		let device = match kind {
+			DeviceKind::Sink => {
+				query_device!(
+					self,
+					kind,
+					get_sink_info_by_index(index),
+					introspect::SinkInfo
+				)
+			}
+			DeviceKind::Source => {
+				query_device!(
+					self,
+					kind,
+					get_source_info_by_index(index),
+					introspect::SourceInfo
+				)
+			}
+		};
+		Ok(device?)
+	}
+
+	fn set_volume_by_index(
+		&mut self,
+		kind: DeviceKind,
+		index: u32,
+		volume: &ChannelVolumes,
+	) -> anyhow::Result<()> {
+		let op = match kind {
+			DeviceKind::Sink => self
+				.introspect
+				.set_sink_volume_by_index(index, volume, None),
+			DeviceKind::Source => self
+				.introspect
+				.set_source_volume_by_index(index, volume, None),
+		};
+		Ok(self.wait_for_operation(op)?)
+	}
+
+	fn set_mute_by_index(
+		&mut self,
+		kind: DeviceKind,
+		index: u32,
+		mute: bool,
+	) -> anyhow::Result<()> {
+		let op = match kind {
+			DeviceKind::Sink => self.introspect.set_sink_mute_by_index(index, mute, None),
+			DeviceKind::Source => self.introspect.set_source_mute_by_index(index, mute, None),
+		};
+		Ok(self.wait_for_operation(op)?)
+	}
+
+	fn play_sound_by_index(&mut self, index: u32, path: &Path) -> anyhow::Result<()> {
+		let mut command = Command::new("paplay");
+		if let Some(server) = &self.server {
+			command.arg(format!("--server={}", server));
+		}
+		command.arg(format!("--device={}", index)).arg(path);
+		self.sound_player.play(command)?;
+		Ok(())
+	}
+}
+
+impl Drop for VolumeController {
+	fn drop(&mut self) {
+		self.context.borrow_mut().disconnect();
+		self.mainloop.borrow_mut().quit(pulse::def::Retval(0));
+	}
+}
+
+/// Spawns a thread with its own PulseAudio connection, which sends the result
+/// of `query` each time one of the facilities in the `mask` changes. Only
+/// sends the value when it differs from the previous one.
+pub fn spawn_listener<T, F>(mask: InterestMaskSet, sender: Sender<T>, query: F)
+where
+	T: PartialEq + Clone + Send + 'static,
+	F: Fn(&mut VolumeController) -> anyhow::Result<T> + Send + 'static,
+{
+	thread::spawn(move || {
+		let mut previous: Option<T> = None;
+		loop {
+			let result = VolumeController::create_with_backoff().and_then(|mut ctrl| {
+				ctrl.listen(mask, |ctrl| match query(ctrl) {
+					Ok(value) if previous.as_ref() != Some(&value) => {
+						previous = Some(value.clone());
+						sender.send_blocking(value).is_ok()
+					}
+					Ok(_) => true,
+					Err(e) => {
+						eprintln!("PulseAudio listener query failed: {}", e);
+						true
+					}
+				})
+			});
+			if sender.is_closed() {
+				return;
+			}
+			if let Err(e) = result {
+				eprintln!("PulseAudio listener stopped: {}. Restarting...", e);
+			}
+			sleep(LISTENER_RESTART_DELAY);
+		}
+	});
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::fs;
+	use std::path::PathBuf;
+	use std::process::{Child, Stdio};
+	use std::sync::atomic::{AtomicUsize, Ordering};
+	use std::time::Instant;
+
+	/// A private PulseAudio daemon with a null sink, listening on its own socket
+	struct TestServer {
+		child: Child,
+		dir: PathBuf,
+		server: String,
+	}
+
+	impl TestServer {
+		/// Panics when PulseAudio isn't installed or doesn't start
+		fn start() -> Self {
+			static COUNT: AtomicUsize = AtomicUsize::new(0);
+			let dir = std::env::temp_dir().join(format!(
+				"swayosd-pulse-{}-{}",
+				std::process::id(),
+				COUNT.fetch_add(1, Ordering::Relaxed)
+			));
+			fs::create_dir_all(&dir).unwrap();
+			let socket = dir.join("native");
+			let child = Command::new("pulseaudio")
+				.args([
+					"--daemonize=no",
+					"-n",
+					"--exit-idle-time=-1",
+					"--use-pid-file=no",
+					"--disable-shm=yes",
+				])
+				.arg(format!(
+					"--load=module-native-protocol-unix auth-anonymous=1 socket={}",
+					socket.display()
+				))
+				.arg("--load=module-null-sink sink_name=swayosd_test")
+				.env("HOME", &dir)
+				.env("XDG_RUNTIME_DIR", &dir)
+				.env("XDG_CONFIG_HOME", &dir)
+				.stdout(Stdio::null())
+				.stderr(Stdio::null())
+				.spawn()
+				.expect("PulseAudio isn't installed");
+			let server = Self {
+				child,
+				dir,
+				server: format!("unix:{}", socket.display()),
+			};
+
+			let start = Instant::now();
+			while server.connect().is_err() {
+				assert!(
+					start.elapsed() < Duration::from_secs(5),
+					"PulseAudio didn't start"
+				);
+				sleep(Duration::from_millis(20));
+			}
+			server
+		}
+
+		fn connect(&self) -> Result<VolumeController, PulseError> {
+			VolumeController::connect(Some(&self.server))
+		}
+
+		/// Writes a silent mono WAV file and returns its path
+		fn write_silence(&self, seconds: u32) -> PathBuf {
+			const RATE: u32 = 8000;
+			let data_len = RATE * 2 * seconds;
+			let mut wav = Vec::new();
+			wav.extend_from_slice(b"RIFF");
+			wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+			wav.extend_from_slice(b"WAVEfmt ");
+			wav.extend_from_slice(&16_u32.to_le_bytes());
+			// PCM, mono
+			wav.extend_from_slice(&1_u16.to_le_bytes());
+			wav.extend_from_slice(&1_u16.to_le_bytes());
+			wav.extend_from_slice(&RATE.to_le_bytes());
+			wav.extend_from_slice(&(RATE * 2).to_le_bytes());
+			// 16 bit samples
+			wav.extend_from_slice(&2_u16.to_le_bytes());
+			wav.extend_from_slice(&16_u16.to_le_bytes());
+			wav.extend_from_slice(b"data");
+			wav.extend_from_slice(&data_len.to_le_bytes());
+			wav.resize(wav.len() + data_len as usize, 0);
+
+			let path = self.dir.join("silence.wav");
+			fs::write(&path, wav).unwrap();
+			path
+		}
+	}
+
+	fn count_sink_inputs(ctrl: &VolumeController) -> usize {
+		let count = Rc::new(Cell::new(0));
+		let op = ctrl.introspect.get_sink_input_info_list(clone!(
+			#[strong]
+			count,
+			move |list| {
+				if let ListResult::Item(_) = list {
+					count.set(count.get() + 1);
+				}
+			}
+		));
+		ctrl.wait_for_operation(op).unwrap();
+		count.get()
+	}
+
+	impl Drop for TestServer {
+		fn drop(&mut self) {
+			let _ = self.child.kill();
+			let _ = self.child.wait();
+			let _ = fs::remove_dir_all(&self.dir);
+		}
+	}
+
+	#[test]
+	fn recording_names_skip_monitors() {
+		let stream = |source, corked, name: &str| RecordingStream {
+			source,
+			corked,
+			name: name.to_string(),
+		};
+		let streams = [
+			stream(1, false, "Firefox"),
+			stream(2, false, "OBS"),
+			stream(1, false, "Firefox"),
+			stream(3, true, "Discord"),
+			stream(4, false, "pavucontrol"),
+		];
+		assert_eq!(
+			get_recording_names(&streams, &[2, 4]),
+			vec!["Firefox".to_string()]
+		);
+		assert!(get_recording_names(&[], &[]).is_empty());
+	}
+
+	#[test]
+	fn connect_fails_fast_without_server() {
+		let start = Instant::now();
+		assert!(VolumeController::connect(Some("unix:/nonexistent/swayosd/native")).is_err());
+		assert!(start.elapsed() < Duration::from_secs(1));
+	}
+
+	#[test]
+	#[ignore = "needs the pulseaudio daemon"]
+	fn reconnects_after_server_restart() {
+		let server = TestServer::start();
+		let mut ctrl = server.connect().unwrap();
+		assert!(ctrl.is_connected());
+
+		drop(server);
+		let start = Instant::now();
+		while ctrl.is_connected() {
+			assert!(start.elapsed() < Duration::from_secs(5));
+			sleep(Duration::from_millis(20));
+		}
+
+		let server = TestServer::start();
+		let mut ctrl = server.connect().unwrap();
+		assert!(ctrl.is_connected());
+		let device = ctrl.get_default_device(DeviceKind::Sink).unwrap();
+		assert_eq!(device.name, "swayosd_test");
+	}
+
+	#[test]
+	#[ignore = "needs the pulseaudio daemon and paplay"]
+	fn feedback_sounds_dont_stack() {
+		let server = TestServer::start();
+		let path = server.write_silence(2);
+		let mut ctrl = server.connect().unwrap();
+		let sink = ctrl.get_default_device(DeviceKind::Sink).unwrap();
+
+		for _ in 0..3 {
+			ctrl.play_sound_by_index(sink.index, &path).unwrap();
+		}
+		let start = Instant::now();
+		while count_sink_inputs(&ctrl) == 0 {
+			assert!(start.elapsed() < Duration::from_secs(5));
+			sleep(Duration::from_millis(20));
+		}
+		sleep(Duration::from_millis(200));
+		assert_eq!(count_sink_inputs(&ctrl), 1);
+	}
+}
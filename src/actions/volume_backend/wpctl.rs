@@ -0,0 +1,482 @@
+use super::{
+	parse_battery, DeviceInfo, DeviceKind, SoundPlayer, VolumeBackend, VolumeBackendConstructor,
+};
+
+use anyhow::{bail, Context};
+use pulse::{
+	channelmap::{self, Position},
+	volume::{ChannelVolumes, Volume, VolumeLinear},
+};
+use std::{path::Path, process::Command};
+
+/// The default programs
+const WPCTL_PROGRAM: &str = "wpctl";
+const PW_CLI_PROGRAM: &str = "pw-cli";
+
+/// Native PipeWire backend driven through the WirePlumber `wpctl` CLI.
+/// Note: `wpctl` only exposes the average volume, so the per-channel volumes
+/// are read and written through `pw-cli`. Devices are treated as mono when
+/// `pw-cli` isn't available.
+pub(super) struct WpCtl {
+	wpctl: Box<Path>,
+	pw_cli: Box<Path>,
+	sound_player: SoundPlayer,
+}
+
+/// The per-channel volumes (linear) and channel positions of a node
+#[derive(Debug, PartialEq)]
+struct ChannelProps {
+	volumes: Vec<f32>,
+	positions: Vec<String>,
+}
+
+/// Parses the channel volumes and map of the first Props object in the output
+/// of `pw-cli enum-params <id> Props`. Ex:
+/// ```text
+///     Prop: key Spa:Pod:Object:Param:Props:channelVolumes (65544), flags 00000000
+///       Array: child.size 4, child.type Spa:Float
+///         Float 0.064000
+///         Float 0.064000
+///     Prop: key Spa:Pod:Object:Param:Props:channelMap (65547), flags 00000000
+///       Array: child.size 4, child.type Spa:Id
+///         Id 3        (Spa:Enum:AudioChannel:FL)
+///         Id 4        (Spa:Enum:AudioChannel:FR)
+/// ```
+fn parse_channel_props(output: &str) -> Option<ChannelProps> {
+	let mut volumes = Vec::new();
+	let mut positions = Vec::new();
+	let mut key = "";
+	for line in output.lines().map(str::trim) {
+		if line.starts_with("Object:") && !volumes.is_empty() {
+			break;
+		} else if let Some(prop) = line.strip_prefix("Prop: key ") {
+			key = prop
+				.split_whitespace()
+				.next()
+				.and_then(|prop| prop.rsplit(':').next())
+				.unwrap_or_default();
+		} else if key == "channelVolumes"
+			&& let Some(value) = line.strip_prefix("Float ")
+		{
+			volumes.push(value.trim().parse::<f32>().ok()?);
+		} else if key == "channelMap" && line.starts_with("Id ") {
+			let name = line.rsplit(':').next()?.trim_end_matches(')');
+			positions.push(name.to_string());
+		}
+	}
+
+	if volumes.is_empty() || volumes.len() != positions.len() {
+		return None;
+	}
+	Some(ChannelProps { volumes, positions })
+}
+
+/// Returns the ID of the node with the name in the output of `pw-cli ls Node`.
+/// Ex:
+/// ```text
+///     id 56, type PipeWire:Interface:Node/3
+///         object.serial = "56"
+///         node.name = "alsa_output.pci-0000_00_1f.3.analog-stereo"
+/// ```
+fn parse_node_id(output: &str, name: &str) -> Option<u32> {
+	let mut id = None;
+	for line in output.lines().map(str::trim) {
+		if let Some(object) = line.strip_prefix("id ") {
+			id = object
+				.split(',')
+				.next()
+				.and_then(|id| id.trim().parse::<u32>().ok());
+		} else if let Some((key, value)) = line.split_once('=')
+			&& key.trim() == "node.name"
+			&& value.trim().trim_matches('"') == name
+		{
+			return id;
+		}
+	}
+	None
+}
+
+/// Converts the PipeWire channel name to the PulseAudio channel position
+fn channel_position(name: &str) -> Position {
+	match name {
+		"MONO" => Position::Mono,
+		"FL" => Position::FrontLeft,
+		"FR" => Position::FrontRight,
+		"FC" => Position::FrontCenter,
+		"LFE" => Position::Lfe,
+		"SL" => Position::SideLeft,
+		"SR" => Position::SideRight,
+		"FLC" => Position::FrontLeftOfCenter,
+		"FRC" => Position::FrontRightOfCenter,
+		"RC" => Position::RearCenter,
+		"RL" => Position::RearLeft,
+		"RR" => Position::RearRight,
+		"TC" => Position::TopCenter,
+		"TFL" => Position::TopFrontLeft,
+		"TFC" => Position::TopFrontCenter,
+		"TFR" => Position::TopFrontRight,
+		"TRL" => Position::TopRearLeft,
+		"TRC" => Position::TopRearCenter,
+		"TRR" => Position::TopRearRight,
+		_ => Position::Aux0,
+	}
+}
+
+impl WpCtl {
+	fn new(wpctl: &Path, pw_cli: &Path) -> anyhow::Result<Self> {
+		// Make sure that wpctl is installed and can reach PipeWire
+		Self::run(wpctl, &["status"])?;
+		Ok(Self {
+			wpctl: wpctl.into(),
+			pw_cli: pw_cli.into(),
+			sound_player: SoundPlayer::default(),
+		})
+	}
+
+	fn run(program: &Path, args: &[&str]) -> anyhow::Result<String> {
+		let output = Command::new(program).args(args).output()?;
+		if !output.status.success() {
+			bail!(
+				"{} {} failed: {}",
+				program.display(),
+				args.join(" "),
+				String::from_utf8_lossy(&output.stderr).trim()
+			);
+		}
+		Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+	}
+
+	/// Looks up the device by its PipeWire ID or wpctl alias
+	fn get_device(&self, kind: DeviceKind, id: &str) -> anyhow::Result<DeviceInfo> {
+		// Ex: "id 56, type PipeWire:Interface:Node"
+		let inspect = Self::run(&self.wpctl, &["inspect", id])?;
+		let index = inspect
+			.lines()
+			.next()
+			.and_then(|line| line.strip_prefix("id "))
+			.and_then(|line| line.split(',').next())
+			.and_then(|index| index.trim().parse::<u32>().ok())
+			.with_context(|| format!("Could not parse the ID of {:?} {}", kind, id))?;
+
+		// Ex: "Volume: 0.40 [MUTED]"
+		let output = Self::run(&self.wpctl, &["get-volume", &index.to_string()])?;
+		let value = output
+			.split_whitespace()
+			.nth(1)
+			.and_then(|value| value.parse::<f64>().ok())
+			.with_context(|| format!("Could not parse the volume of {:?} {}", kind, id))?;
+		let mute = output.contains("[MUTED]");
+
+		let (volume, channel_map) = self.get_channels(index).unwrap_or_else(|| {
+			let mut volume = ChannelVolumes::default();
+			volume.set(
+				1,
+				Volume((value * f64::from(Volume::NORMAL.0)).round() as u32),
+			);
+			let mut channel_map = channelmap::Map::default();
+			channel_map.init_mono();
+			(volume, channel_map)
+		});
+
+		let is_bluetooth =
+			Self::get_property(&inspect, "device.bus").as_deref() == Some("bluetooth");
+		let battery = Self::get_property(&inspect, "bluetooth.battery")
+			.and_then(|value| parse_battery(&value))
+			.filter(|_| is_bluetooth);
+		let bluetooth_address =
+			Self::get_property(&inspect, "api.bluez5.address").filter(|_| is_bluetooth);
+
+		Ok(DeviceInfo {
+			kind,
+			index,
+			name: Self::get_property(&inspect, "node.name").unwrap_or_default(),
+			volume,
+			channel_map,
+			mute,
+			battery,
+			bluetooth_address,
+		})
+	}
+
+	/// Returns the per-channel volumes and channel map of the node
+	fn get_channels(&self, index: u32) -> Option<(ChannelVolumes, channelmap::Map)> {
+		let output = Self::run(&self.pw_cli, &["enum-params", &index.to_string(), "Props"]).ok()?;
+		let props = parse_channel_props(&output)?;
+		let channels = u8::try_from(props.volumes.len())
+			.ok()
+			.filter(|channels| *channels <= ChannelVolumes::CHANNELS_MAX)?;
+
+		let mut volume = ChannelVolumes::default();
+		volume.set_len(channels);
+		for (volume, value) in volume.get_mut().iter_mut().zip(&props.volumes) {
+			*volume = Volume::from(VolumeLinear(f64::from(*value)));
+		}
+		let mut channel_map = channelmap::Map::default();
+		channel_map.set_len(channels);
+		for (position, name) in channel_map.get_mut().iter_mut().zip(&props.positions) {
+			*position = channel_position(name);
+		}
+		Some((volume, channel_map))
+	}
+
+	/// Looks up a property in the output of `wpctl inspect`.
+	/// Ex: `  * device.bus = "bluetooth"`
+	fn get_property(inspect: &str, key: &str) -> Option<String> {
+		inspect.lines().find_map(|line| {
+			let (name, value) = line.trim().trim_start_matches('*').split_once('=')?;
+			(name.trim() == key).then(|| value.trim().trim_matches('"').to_string())
+		})
+	}
+}
+
+impl VolumeBackendConstructor for WpCtl {
+	fn try_new() -> anyhow::Result<Self> {
+		Self::new(Path::new(WPCTL_PROGRAM), Path::new(PW_CLI_PROGRAM))
+	}
+}
+
+impl VolumeBackend for WpCtl {
+	fn get_default_device(&mut self, kind: DeviceKind) -> anyhow::Result<DeviceInfo> {
+		let alias = match kind {
+			DeviceKind::Sink => "@DEFAULT_AUDIO_SINK@",
+			DeviceKind::Source => "@DEFAULT_AUDIO_SOURCE@",
+		};
+		self.get_device(kind, alias)
+	}
+
+	fn get_device_by_name(&mut self, kind: DeviceKind, name: &str) -> anyhow::Result<DeviceInfo> {
+		// wpctl only accepts IDs and its aliases, so look up the ID of the node name
+		if name.parse::<u32>().is_ok() || name.starts_with('@') {
+			return self.get_device(kind, name);
+		}
+		let nodes = Self::run(&self.pw_cli, &["ls", "Node"])?;
+		let id = parse_node_id(&nodes, name)
+			.with_context(|| format!("{:?} {} does not exist", kind, name))?;
+		self.get_device(kind, &id.to_string())
+	}
+
+	fn get_device_by_index(&mut self, kind: DeviceKind, index: u32) -> anyhow::Result<DeviceInfo> {
+		self.get_device(kind, &index.to_string())
+	}
+
+	fn set_volume_by_index(
+		&mut self,
+		_kind: DeviceKind,
+		index: u32,
+		volume: &ChannelVolumes,
+	) -> anyhow::Result<()> {
+		if volume.len() > 1 {
+			let volumes = volume
+				.get()
+				.iter()
+				.map(|volume| format!("{:.6}", VolumeLinear::from(*volume).0))
+				.collect::<Vec<_>>();
+			let props = format!("{{ channelVolumes: [ {} ] }}", volumes.join(", "));
+			Self::run(
+				&self.pw_cli,
+				&["set-param", &index.to_string(), "Props", &props],
+			)?;
+			return Ok(());
+		}
+
+		let value = f64::from(volume.avg().0) / f64::from(Volume::NORMAL.0);
+		Self::run(
+			&self.wpctl,
+			&["set-volume", &index.to_string(), &format!("{:.4}", value)],
+		)?;
+		Ok(())
+	}
+
+	fn set_mute_by_index(
+		&mut self,
+		_kind: DeviceKind,
+		index: u32,
+		mute: bool,
+	) -> anyhow::Result<()> {
+		let mute = if mute { "1" } else { "0" };
+		Self::run(&self.wpctl, &["set-mute", &index.to_string(), mute])?;
+		Ok(())
+	}
+
+	fn play_sound_by_index(&mut self, index: u32, path: &Path) -> anyhow::Result<()> {
+		let mut command = Command::new("pw-play");
+		command.arg(format!("--target={}", index)).arg(path);
+		self.sound_player.play(command)?;
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::{fs, os::unix::fs::PermissionsExt, path::PathBuf};
+
+	const ENUM_PARAMS: &str = "  Object: size 1096, type Spa:Pod:Object:Param:Props (262146), id Spa:Enum:ParamId:Props (2)
+    Prop: key Spa:Pod:Object:Param:Props:volume (65539), flags 00000000
+      Float 1.000000
+    Prop: key Spa:Pod:Object:Param:Props:mute (65540), flags 00000000
+      Bool false
+    Prop: key Spa:Pod:Object:Param:Props:channelVolumes (65544), flags 00000000
+      Array: child.size 4, child.type Spa:Float
+        Float 0.064000
+        Float 0.125000
+    Prop: key Spa:Pod:Object:Param:Props:channelMap (65547), flags 00000000
+      Array: child.size 4, child.type Spa:Id
+        Id 3        (Spa:Enum:AudioChannel:FL)
+        Id 4        (Spa:Enum:AudioChannel:FR)
+    Prop: key Spa:Pod:Object:Param:Props:softVolumes (65555), flags 00000000
+      Array: child.size 4, child.type Spa:Float
+        Float 1.000000
+        Float 1.000000
+  Object: size 232, type Spa:Pod:Object:Param:Props (262146), id Spa:Enum:ParamId:Props (2)
+    Prop: key Spa:Pod:Object:Param:Props:channelVolumes (65544), flags 00000000
+      Array: child.size 4, child.type Spa:Float
+        Float 1.000000
+";
+
+	const NODES: &str = "	id 0, type PipeWire:Interface:Core/4
+ 		object.serial = \"0\"
+ 		core.name = \"pipewire-0\"
+	id 56, type PipeWire:Interface:Node/3
+ 		object.serial = \"56\"
+ 		factory.id = \"18\"
+ 		node.description = \"USB Headset\"
+ 		node.name = \"alsa_output.usb-headset\"
+ 		media.class = \"Audio/Sink\"
+	id 57, type PipeWire:Interface:Node/3
+ 		object.serial = \"57\"
+ 		node.name = \"alsa_input.usb-headset\"
+ 		media.class = \"Audio/Source\"
+";
+
+	const INSPECT: &str = "id 56, type PipeWire:Interface:Node
+    factory.id = \"18\"
+  * node.description = \"USB Headset\"
+  * node.name = \"alsa_output.usb-headset\"
+    media.class = \"Audio/Sink\"
+";
+
+	/// Writes a fake wpctl and pw-cli, which log their arguments and store the
+	/// volume of node 56 in the directory. Returns their paths
+	fn fake_wpctl(test_name: &str) -> (PathBuf, PathBuf) {
+		let dir = std::env::temp_dir().join(format!(
+			"swayosd-wpctl-{}-{}",
+			test_name,
+			std::process::id()
+		));
+		fs::create_dir_all(&dir).unwrap();
+		fs::write(dir.join("nodes"), NODES).unwrap();
+		fs::write(dir.join("inspect"), INSPECT).unwrap();
+		fs::write(dir.join("volume"), "0.40").unwrap();
+		let wpctl = format!(
+			r#"#!/bin/sh
+dir="{}"
+echo "wpctl $*" >> "$dir/calls"
+case "$*" in
+	"status") ;;
+	"inspect 56"|"inspect @DEFAULT_AUDIO_SINK@") cat "$dir/inspect" ;;
+	"get-volume 56") echo "Volume: $(cat "$dir/volume")" ;;
+	"set-volume 56 "*) echo "$3" > "$dir/volume" ;;
+	*) echo "Object not found" >&2; exit 1 ;;
+esac
+"#,
+			dir.display()
+		);
+		let pw_cli = format!(
+			r#"#!/bin/sh
+dir="{}"
+echo "pw-cli $*" >> "$dir/calls"
+case "$*" in
+	"ls Node") cat "$dir/nodes" ;;
+	*) echo "unknown command" >&2; exit 1 ;;
+esac
+"#,
+			dir.display()
+		);
+		let programs = (dir.join("wpctl"), dir.join("pw-cli"));
+		for (program, script) in [(&programs.0, wpctl), (&programs.1, pw_cli)] {
+			fs::write(program, script).unwrap();
+			fs::set_permissions(program, fs::Permissions::from_mode(0o755)).unwrap();
+		}
+		programs
+	}
+
+	fn read_calls(program: &Path) -> Vec<String> {
+		let calls = fs::read_to_string(program.with_file_name("calls")).unwrap_or_default();
+		calls.lines().map(str::to_string).collect()
+	}
+
+	#[test]
+	fn parses_node_id() {
+		assert_eq!(parse_node_id(NODES, "alsa_output.usb-headset"), Some(56));
+		assert_eq!(parse_node_id(NODES, "alsa_input.usb-headset"), Some(57));
+		assert_eq!(parse_node_id(NODES, "pipewire-0"), None);
+		assert_eq!(parse_node_id(NODES, "alsa_output"), None);
+		assert_eq!(parse_node_id("", "alsa_output.usb-headset"), None);
+	}
+
+	#[test]
+	fn gets_device_by_node_name() {
+		let (wpctl, pw_cli) = fake_wpctl("name");
+		let mut backend = WpCtl::new(&wpctl, &pw_cli).unwrap();
+
+		let device = backend
+			.get_device_by_name(DeviceKind::Sink, "alsa_output.usb-headset")
+			.unwrap();
+		assert_eq!(device.index, 56);
+		assert_eq!(device.name, "alsa_output.usb-headset");
+		assert_eq!(device.volume.max(), Volume(26214));
+		assert!(!device.mute);
+		// wpctl only gets the ID of the node
+		assert!(read_calls(&wpctl).contains(&"wpctl inspect 56".to_string()));
+		assert!(!read_calls(&wpctl)
+			.iter()
+			.any(|call| call.contains("alsa_output")));
+
+		let default = backend.get_default_device(DeviceKind::Sink).unwrap();
+		assert_eq!(default.index, 56);
+		let mut volume = ChannelVolumes::default();
+		volume.set(1, Volume(Volume::NORMAL.0 / 2));
+		backend
+			.set_volume_by_index(DeviceKind::Sink, 56, &volume)
+			.unwrap();
+		let device = backend.get_device_by_name(DeviceKind::Sink, "56").unwrap();
+		assert_eq!(device.volume.max(), Volume(Volume::NORMAL.0 / 2));
+
+		assert!(backend
+			.get_device_by_name(DeviceKind::Sink, "alsa_output.pci")
+			.is_err());
+
+		fs::remove_dir_all(wpctl.parent().unwrap()).unwrap();
+	}
+
+	#[test]
+	fn parses_channel_props() {
+		assert_eq!(
+			parse_channel_props(ENUM_PARAMS),
+			Some(ChannelProps {
+				volumes: vec![0.064, 0.125],
+				positions: vec!["FL".to_string(), "FR".to_string()],
+			})
+		);
+	}
+
+	#[test]
+	fn rejects_incomplete_channel_props() {
+		assert_eq!(parse_channel_props(""), None);
+		let without_map = ENUM_PARAMS
+			.lines()
+			.filter(|line| !line.contains("Id "))
+			.collect::<Vec<_>>()
+			.join("\n");
+		assert_eq!(parse_channel_props(&without_map), None);
+	}
+
+	#[test]
+	fn maps_channel_positions() {
+		assert_eq!(channel_position("FL"), Position::FrontLeft);
+		assert_eq!(channel_position("FR"), Position::FrontRight);
+		assert_eq!(channel_position("MONO"), Position::Mono);
+		assert_eq!(channel_position("AUX7"), Position::Aux0);
+	}
+}
@@ -11,6 +11,7 @@ pub enum ArgFlags {
 	CustomProgressText,
 	MinBrightness,
 	Duration,
+	IgnoreVolumeBarrier,
 }
 
 impl fmt::Display for ArgFlags {
@@ -24,6 +25,7 @@ impl fmt::Display for ArgFlags {
 			ArgFlags::CustomProgressText => "CUSTOM-PROGRESS-TEXT",
 			ArgFlags::MinBrightness => "MIN-BRIGHTNESS",
 			ArgFlags::Duration => "DURATION",
+			ArgFlags::IgnoreVolumeBarrier => "IGNORE-VOLUME-BARRIER",
 		};
 		write!(f, "{}", string)
 	}
@@ -42,6 +44,7 @@ impl str::FromStr for ArgFlags {
 			"CUSTOM-PROGRESS-TEXT" => ArgFlags::CustomProgressText,
 			"MIN-BRIGHTNESS" => ArgFlags::MinBrightness,
 			"DURATION" => ArgFlags::Duration,
+			"IGNORE-VOLUME-BARRIER" => ArgFlags::IgnoreVolumeBarrier,
 			other_type => return Err(other_type.to_owned()),
 		};
 		Ok(result)
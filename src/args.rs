@@ -22,6 +22,15 @@ fn value_parser_volume(s: &str) -> Result<String, String> {
 	}
 }
 
+fn value_parser_balance(s: &str) -> Result<String, String> {
+	match (s, s.parse::<i8>()) {
+		// Parse custom step values
+		(_, Ok(num)) => Ok(num.to_string()),
+		("left", _) | ("right", _) | ("center", _) => Ok(s.into()),
+		(e, _) => Err(format!("Unknown output balance mode: \"{}\"!...", e)),
+	}
+}
+
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
 #[command(arg_required_else_help(false))]
@@ -105,24 +114,49 @@ pub struct ArgsClient {
 	)]
 	pub input_volume: Option<String>,
 
+	/// Shows balance osd and shifts the default sink balance to the left, right or center
+	#[arg(
+		long,
+		value_name = "left|right|center|(±)number",
+		allow_negative_numbers = true,
+		value_parser = value_parser_balance,
+	)]
+	pub output_balance: Option<String>,
+
 	/// Sets the maximum Volume
 	#[arg(long, value_name = "(+)number")]
 	pub max_volume: Option<String>,
 
+	/// Raise the volume past 100% even if the volume barrier is enabled
+	#[arg(long, default_value_t = false)]
+	pub ignore_volume_barrier: bool,
+
 	/// For which device to increase/decrease audio/brightness.
-	/// Can be wildcard for brightness.
+	/// Can be wildcard for brightness, or "all" to change all brightness devices.
+	/// LED class devices can be changed with "leds:NAME" (/sys/class/leds/NAME).
 	/// Ex: (pactl list short sinks|sources)/(brightnessctl -l -c backlight).
 	#[arg(long, value_name = "Pulseaudio/BrightnessCtl device name")]
 	pub device: Option<String>,
 
-	/// Shows brightness osd and raises or lowers all available sources of brightness device
+	/// Shows brightness osd and raises or lowers all available sources of brightness device.
+	/// "auto-toggle" turns the ambient light auto brightness on or off, and "cycle"
+	/// changes to the next brightness preset
 	#[arg(
 		long,
-		value_name = "raise|lower|(±)number",
+		value_name = "raise|lower|auto-toggle|cycle|(±)number",
 		allow_negative_numbers = true
 	)]
 	pub brightness: Option<String>,
 
+	/// Shows the keyboard backlight osd and raises, lowers, toggles or sets the
+	/// keyboard backlight through UPower
+	#[arg(
+		long,
+		value_name = "raise|lower|toggle|(±)number",
+		allow_negative_numbers = true
+	)]
+	pub kbd_backlight: Option<String>,
+
 	/// Sets the minimum Brightness
 	#[arg(long, value_name = "(+)number")]
 	pub min_brightness: Option<String>,
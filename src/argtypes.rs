@@ -9,6 +9,9 @@ pub enum ArgTypes {
 	SinkVolumeMuteToggle,
 	SinkVolumeMute,
 	SinkVolumeUnMute,
+	SinkBalanceLeft,
+	SinkBalanceRight,
+	SinkBalanceCenter,
 	SourceVolumeRaise,
 	SourceVolumeLower,
 	SourceVolumeMuteToggle,
@@ -17,6 +20,8 @@ pub enum ArgTypes {
 	BrightnessRaise,
 	BrightnessLower,
 	BrightnessSet,
+	BrightnessAutoToggle,
+	BrightnessCycle,
 	NumLock,
 	ScrollLock,
 	CustomMessage,
@@ -24,6 +29,10 @@ pub enum ArgTypes {
 	CustomProgress,
 	CustomSegmentedProgress,
 	KbdBacklight,
+	KbdBacklightRaise,
+	KbdBacklightLower,
+	KbdBacklightToggle,
+	KbdBacklightSet,
 }
 
 impl fmt::Display for ArgTypes {
@@ -35,6 +44,9 @@ impl fmt::Display for ArgTypes {
 			ArgTypes::SinkVolumeMuteToggle => "SINK-VOLUME-MUTE-TOGGLE",
 			ArgTypes::SinkVolumeMute => "SINK-VOLUME-MUTE",
 			ArgTypes::SinkVolumeUnMute => "SINK-VOLUME-UNMUTE",
+			ArgTypes::SinkBalanceLeft => "SINK-BALANCE-LEFT",
+			ArgTypes::SinkBalanceRight => "SINK-BALANCE-RIGHT",
+			ArgTypes::SinkBalanceCenter => "SINK-BALANCE-CENTER",
 			ArgTypes::SourceVolumeRaise => "SOURCE-VOLUME-RAISE",
 			ArgTypes::SourceVolumeLower => "SOURCE-VOLUME-LOWER",
 			ArgTypes::SourceVolumeMuteToggle => "SOURCE-VOLUME-MUTE-TOGGLE",
@@ -43,6 +55,8 @@ impl fmt::Display for ArgTypes {
 			ArgTypes::BrightnessRaise => "BRIGHTNESS-RAISE",
 			ArgTypes::BrightnessLower => "BRIGHTNESS-LOWER",
 			ArgTypes::BrightnessSet => "BRIGHTNESS-SET",
+			ArgTypes::BrightnessAutoToggle => "BRIGHTNESS-AUTO-TOGGLE",
+			ArgTypes::BrightnessCycle => "BRIGHTNESS-CYCLE",
 			ArgTypes::NumLock => "NUM-LOCK",
 			ArgTypes::ScrollLock => "SCROLL-LOCK",
 			ArgTypes::CustomMessage => "CUSTOM-MESSAGE",
@@ -50,6 +64,10 @@ impl fmt::Display for ArgTypes {
 			ArgTypes::CustomProgress => "CUSTOM-PROGRESS",
 			ArgTypes::CustomSegmentedProgress => "CUSTOM-SEGMENTED-PROGRESS",
 			ArgTypes::KbdBacklight => "KBD-BACKLIGHT",
+			ArgTypes::KbdBacklightRaise => "KBD-BACKLIGHT-RAISE",
+			ArgTypes::KbdBacklightLower => "KBD-BACKLIGHT-LOWER",
+			ArgTypes::KbdBacklightToggle => "KBD-BACKLIGHT-TOGGLE",
+			ArgTypes::KbdBacklightSet => "KBD-BACKLIGHT-SET",
 		};
 		write!(f, "{}", string)
 	}
@@ -66,6 +84,9 @@ impl str::FromStr for ArgTypes {
 			"SINK-VOLUME-MUTE-TOGGLE" => ArgTypes::SinkVolumeMuteToggle,
 			"SINK-VOLUME-MUTE" => ArgTypes::SinkVolumeMute,
 			"SINK-VOLUME-UNMUTE" => ArgTypes::SinkVolumeUnMute,
+			"SINK-BALANCE-LEFT" => ArgTypes::SinkBalanceLeft,
+			"SINK-BALANCE-RIGHT" => ArgTypes::SinkBalanceRight,
+			"SINK-BALANCE-CENTER" => ArgTypes::SinkBalanceCenter,
 			"SOURCE-VOLUME-RAISE" => ArgTypes::SourceVolumeRaise,
 			"SOURCE-VOLUME-LOWER" => ArgTypes::SourceVolumeLower,
 			"SOURCE-VOLUME-MUTE-TOGGLE" => ArgTypes::SourceVolumeMuteToggle,
@@ -74,6 +95,8 @@ impl str::FromStr for ArgTypes {
 			"BRIGHTNESS-RAISE" => ArgTypes::BrightnessRaise,
 			"BRIGHTNESS-LOWER" => ArgTypes::BrightnessLower,
 			"BRIGHTNESS-SET" => ArgTypes::BrightnessSet,
+			"BRIGHTNESS-AUTO-TOGGLE" => ArgTypes::BrightnessAutoToggle,
+			"BRIGHTNESS-CYCLE" => ArgTypes::BrightnessCycle,
 			"NUM-LOCK" => ArgTypes::NumLock,
 			"SCROLL-LOCK" => ArgTypes::ScrollLock,
 			"CUSTOM-MESSAGE" => ArgTypes::CustomMessage,
@@ -81,6 +104,10 @@ impl str::FromStr for ArgTypes {
 			"CUSTOM-PROGRESS" => ArgTypes::CustomProgress,
 			"CUSTOM-SEGMENTED-PROGRESS" => ArgTypes::CustomSegmentedProgress,
 			"KBD-BACKLIGHT" => ArgTypes::KbdBacklight,
+			"KBD-BACKLIGHT-RAISE" => ArgTypes::KbdBacklightRaise,
+			"KBD-BACKLIGHT-LOWER" => ArgTypes::KbdBacklightLower,
+			"KBD-BACKLIGHT-TOGGLE" => ArgTypes::KbdBacklightToggle,
+			"KBD-BACKLIGHT-SET" => ArgTypes::KbdBacklightSet,
 			other_type => return Err(other_type.to_owned()),
 		};
 		Ok(result)
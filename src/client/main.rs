@@ -80,6 +80,10 @@ fn parse_args(args: &ArgsClient, proxy: &ServerProxyBlocking<'_>) {
 			Err(_) => eprintln!("{} is not a number between 0 and {}!", value, u8::MAX),
 		}
 	}
+	// Ignore volume barrier
+	if args.ignore_volume_barrier {
+		flags.push((ArgFlags::IgnoreVolumeBarrier, None));
+	}
 	// Custom icon
 	if let Some(value) = args.custom_icon.to_owned() {
 		flags.push((ArgFlags::CustomIcon, Some(value)));
@@ -151,6 +155,12 @@ fn parse_args(args: &ArgsClient, proxy: &ServerProxyBlocking<'_>) {
 	{
 		actions.push(parsed);
 	}
+	// Output balance
+	if let Some(value) = args.output_balance.as_deref()
+		&& let Ok(parsed) = balance_parser(value)
+	{
+		actions.push(parsed);
+	}
 	// Brightness
 	if let Some(value) = args.brightness.as_deref() {
 		// let value: &str = value.as_str();
@@ -164,6 +174,8 @@ fn parse_args(args: &ArgsClient, proxy: &ServerProxyBlocking<'_>) {
 
 			("raise", _) => Some((ArgTypes::BrightnessRaise, None)),
 			("lower", _) => Some((ArgTypes::BrightnessLower, None)),
+			("auto-toggle", _) => Some((ArgTypes::BrightnessAutoToggle, None)),
+			("cycle", _) => Some((ArgTypes::BrightnessCycle, None)),
 			(e, _) => {
 				eprintln!("Unknown brightness mode: \"{}\"!...", e);
 				None
@@ -173,6 +185,28 @@ fn parse_args(args: &ArgsClient, proxy: &ServerProxyBlocking<'_>) {
 			actions.push(value);
 		}
 	}
+	// Keyboard backlight
+	if let Some(value) = args.kbd_backlight.as_deref() {
+		let value = match (value, value.parse::<i32>()) {
+			// Parse custom step values
+			(_, Ok(num)) => match value.get(..1) {
+				Some("+") => Some((ArgTypes::KbdBacklightRaise, Some(num.to_string()))),
+				Some("-") => Some((ArgTypes::KbdBacklightLower, Some(num.abs().to_string()))),
+				_ => Some((ArgTypes::KbdBacklightSet, Some(num.to_string()))),
+			},
+
+			("raise", _) => Some((ArgTypes::KbdBacklightRaise, None)),
+			("lower", _) => Some((ArgTypes::KbdBacklightLower, None)),
+			("toggle", _) => Some((ArgTypes::KbdBacklightToggle, None)),
+			(e, _) => {
+				eprintln!("Unknown keyboard backlight mode: \"{}\"!...", e);
+				None
+			}
+		};
+		if let Some(value) = value {
+			actions.push(value);
+		}
+	}
 	// Playerctl
 	if let Some(value) = args.playerctl.as_deref() {
 		match value {
@@ -256,3 +290,25 @@ fn volume_parser(is_sink: bool, value: &str) -> Result<(ArgTypes, Option<String>
 	}
 	Ok(v)
 }
+
+fn balance_parser(value: &str) -> Result<(ArgTypes, Option<String>), i32> {
+	let v = match (value, value.parse::<i8>()) {
+		// Parse custom step values
+		(_, Ok(num)) => (
+			if num.is_positive() {
+				ArgTypes::SinkBalanceRight
+			} else {
+				ArgTypes::SinkBalanceLeft
+			},
+			Some(num.unsigned_abs().to_string()),
+		),
+		("left", _) => (ArgTypes::SinkBalanceLeft, None),
+		("right", _) => (ArgTypes::SinkBalanceRight, None),
+		("center", _) => (ArgTypes::SinkBalanceCenter, None),
+		(e, _) => {
+			eprintln!("Unknown output balance mode: \"{}\"!...", e);
+			return Err(1);
+		}
+	};
+	Ok(v)
+}
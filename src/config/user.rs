@@ -1,6 +1,7 @@
 use gtk::glib::system_config_dirs;
 use gtk::glib::user_config_dir;
 use serde_derive::Deserialize;
+use std::collections::HashMap;
 use std::error::Error;
 use std::path::Path;
 use std::path::PathBuf;
@@ -9,17 +10,95 @@ use std::path::PathBuf;
 #[serde(deny_unknown_fields)]
 pub struct ClientConfig {}
 
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum VolumeBackendType {
+	PulseAudio,
+	PipeWire,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum BrightnessBackendType {
+	BrightnessCtl,
+	/// Writes to /sys/class/backlight directly
+	Sysfs,
+	/// Uses the SetBrightness method of the logind session
+	Logind,
+	/// External monitors over DDC/CI, the device name is the monitor connector
+	DdcUtil,
+}
+
+#[derive(Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum VolumeScale {
+	/// Percentage of the linear amplitude
+	Linear,
+	/// The same percentage as pavucontrol and pactl
+	#[default]
+	Cubic,
+	/// Fixed decibel steps
+	Db,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MicMuteIndicator {
+	/// Show the indicator while the default source is muted
+	Muted,
+	/// Show the indicator while the default source is unmuted
+	Unmuted,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct DeviceVolumeLimit {
+	/// The name of the sink or source. Supports `*` and `?` wildcards
+	pub name: String,
+	pub max_volume: u8,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct AutoBrightnessPoint {
+	/// The ambient light level reported by iio-sensor-proxy
+	pub lux: f64,
+	/// The brightness percentage at that light level
+	pub brightness: f64,
+}
+
 #[derive(Deserialize, Default, Debug, Clone)]
 #[serde(deny_unknown_fields)]
 pub struct ServerConfig {
 	pub style: Option<PathBuf>,
 	pub top_margin: Option<f32>,
 	pub max_volume: Option<u8>,
+	pub device_volume_limits: Option<Vec<DeviceVolumeLimit>>,
 	pub show_percentage: Option<bool>,
 	pub playerctl_format: Option<String>,
 	pub min_brightness: Option<u32>,
+	pub brightness_exponent: Option<f64>,
+	pub brightness_fade_duration: Option<u64>,
+	pub brightness_presets: Option<Vec<u32>>,
+	pub brightness_backend: Option<Vec<BrightnessBackendType>>,
+	pub monitor_backlights: Option<HashMap<String, String>>,
 	pub keyboard_backlight: Option<bool>,
+	pub watch_backlight: Option<bool>,
+	pub auto_brightness: Option<bool>,
+	pub auto_brightness_active: Option<bool>,
+	pub auto_brightness_curve: Option<Vec<AutoBrightnessPoint>>,
 	pub duration: Option<u64>,
+	pub volume_backend: Option<VolumeBackendType>,
+	pub volume_scale: Option<VolumeScale>,
+	pub volume_barrier: Option<bool>,
+	pub volume_barrier_pause: Option<u64>,
+	pub volume_acceleration: Option<Vec<u32>>,
+	pub brightness_acceleration: Option<Vec<u32>>,
+	pub acceleration_timeout: Option<u64>,
+	pub snap_to_step: Option<bool>,
+	pub mic_mute_indicator: Option<MicMuteIndicator>,
+	pub mic_in_use_indicator: Option<bool>,
+	pub feedback_sound: Option<PathBuf>,
 }
 
 #[derive(Deserialize, Default, Debug)]
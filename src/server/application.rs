@@ -6,30 +6,65 @@ use gtk::{
 		self, ApplicationFlags, BusNameWatcherFlags, BusType, DBusConnection, DBusSignalFlags,
 		ListModel, SignalSubscriptionId,
 	},
-	glib::{clone, Char, ControlFlow, ControlFlow::Break, MainContext, OptionArg, OptionFlags},
+	glib::{
+		self, clone, Char, ControlFlow, ControlFlow::Break, MainContext, OptionArg, OptionFlags,
+	},
 	prelude::*,
 	Application,
 };
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell, RefMut};
+use std::collections::{BTreeMap, HashMap};
 use std::error::Error;
+use std::path::PathBuf;
 use std::rc::Rc;
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use pulse::context::subscribe::InterestMaskSet;
 
+use crate::actions::brightness_backend::{self, BrightnessBackendCache};
 use crate::actions::mpris::{Playerctl, PlayerctlAction, PlayerctlDeviceRaw};
-use crate::actions::pulse::{DeviceKind, VolumeController};
+use crate::actions::volume_backend::{
+	pulseaudio, DeviceInfo, DeviceKind, VolumeBackend, VolumeConnection,
+};
 use crate::argflags::ArgFlags;
 use crate::args::ArgsServer;
 use crate::argtypes::ArgTypes;
-use crate::config::{self, user::ServerConfig, APPLICATION_NAME, DBUS_BACKEND_NAME};
+use crate::config::{
+	self,
+	user::{BrightnessBackendType, DeviceVolumeLimit, MicMuteIndicator, ServerConfig, VolumeScale},
+	APPLICATION_NAME, DBUS_BACKEND_NAME,
+};
 use crate::global_utils;
+use crate::indicator_window::{Indicator, IndicatorWindow};
 use crate::osd_window::SwayosdWindow;
 use crate::utils::*;
-use crate::{login1, upower, DbusSenderFlagsType, DbusSenderType};
+use crate::{bluez, login1, sensor_proxy, upower, DbusSenderFlagsType, DbusSenderType};
+
+/// The time in milliseconds between the steps of a brightness fade
+const BRIGHTNESS_FADE_INTERVAL: u64 = 25;
+
+/// For how long in milliseconds to ignore external backlight changes after
+/// changing the brightness ourselves
+const BRIGHTNESS_CHANGE_IGNORE_TIMEOUT: u64 = 1000;
+
+/// How much the ambient light brightness has to differ from the current
+/// brightness in percent before changing it, to avoid constant small changes
+const AUTO_BRIGHTNESS_THRESHOLD: f64 = 2.0;
+
+/// The default time in milliseconds between repeated actions for them to be accelerated
+const DEFAULT_ACCELERATION_TIMEOUT: u64 = 300;
 
 #[derive(Clone)]
 pub struct ActionOptions {
 	pub max_volume: ActionField<u8>,
+	pub device_volume_limits: ActionField<Vec<DeviceVolumeLimit>>,
 	pub min_brightness: ActionField<u32>,
+	pub brightness_exponent: ActionField<f64>,
+	pub brightness_fade_duration: ActionField<u64>,
+	pub brightness_presets: ActionField<Vec<u32>>,
+	pub brightness_backends: ActionField<Vec<BrightnessBackendType>>,
+	pub monitor_backlights: ActionField<HashMap<String, String>>,
 	pub device_name: ActionOptionalField<String>,
 	pub monitor_name: ActionOptionalField<String>,
 	pub icon_name: ActionOptionalField<String>,
@@ -38,13 +73,27 @@ pub struct ActionOptions {
 	pub top_margin: ActionField<f32>,
 	pub duration: ActionField<u64>,
 	pub show_percentage: ActionField<bool>,
+	pub volume_scale: ActionField<VolumeScale>,
+	pub volume_barrier: ActionField<bool>,
+	pub volume_barrier_pause: ActionField<u64>,
+	pub step_multiplier: ActionField<u32>,
+	pub snap_to_step: ActionField<bool>,
 }
 
 impl ActionOptions {
 	pub fn new() -> Self {
 		Self {
 			max_volume: ActionField::new(100_u8),
+			device_volume_limits: ActionField::new(Vec::new()),
 			min_brightness: ActionField::new(5_u32),
+			brightness_exponent: ActionField::new(1.0),
+			brightness_fade_duration: ActionField::new(0),
+			brightness_presets: ActionField::new(vec![10, 40, 70, 100]),
+			brightness_backends: ActionField::new(vec![
+				BrightnessBackendType::BrightnessCtl,
+				BrightnessBackendType::Sysfs,
+			]),
+			monitor_backlights: ActionField::new(HashMap::new()),
 			device_name: ActionOptionalField::new(None),
 			monitor_name: ActionOptionalField::new(None),
 			icon_name: ActionOptionalField::new(None),
@@ -53,6 +102,11 @@ impl ActionOptions {
 			top_margin: ActionField::new(0.85_f32),
 			duration: ActionField::new(1000),
 			show_percentage: ActionField::new(false),
+			volume_scale: ActionField::new(VolumeScale::default()),
+			volume_barrier: ActionField::new(false),
+			volume_barrier_pause: ActionField::new(500),
+			step_multiplier: ActionField::new(1),
+			snap_to_step: ActionField::new(false),
 		}
 	}
 }
@@ -62,11 +116,35 @@ pub struct SwayOSDApplication {
 	#[shrinkwrap(main_field)]
 	app: gtk::Application,
 	windows: Rc<RefCell<Vec<SwayosdWindow>>>,
+	indicator_windows: Rc<RefCell<Vec<IndicatorWindow>>>,
+	/// The currently shown persistent indicators
+	indicators: Rc<RefCell<BTreeMap<&'static str, Indicator>>>,
 	activated: Rc<RefCell<bool>>,
 	_hold: Rc<gio::ApplicationHoldGuard>,
 	action_options: Rc<ActionOptions>,
 
-	volume_ctrl: Rc<RefCell<Option<VolumeController>>>,
+	feedback_sound: Option<PathBuf>,
+	volume_backend: Rc<RefCell<VolumeConnection>>,
+	/// The pending background reconnection to the volume backend
+	volume_reconnect: Rc<RefCell<Option<glib::SourceId>>>,
+	/// The system bus connection, created when it's first needed
+	system_bus: Rc<RefCell<Option<zbus::Connection>>>,
+	/// The BlueZ client, created when it's first needed
+	bluez: Rc<RefCell<Option<Rc<bluez::BlueZ>>>>,
+	/// When the volume of each device was last raised
+	last_volume_raise: Rc<RefCell<HashMap<(DeviceKind, u32), Instant>>>,
+	/// Counts the actions, so that a Bluetooth battery lookup that finishes
+	/// after the next action doesn't show an outdated volume
+	action_count: Rc<Cell<u64>>,
+	brightness_backend_cache: Rc<RefCell<BrightnessBackendCache>>,
+	/// The currently running brightness fade
+	brightness_fade: Rc<RefCell<Option<glib::SourceId>>>,
+	last_brightness_change: Rc<Cell<Option<Instant>>>,
+	/// The brightness of each keyboard backlight before toggling it off
+	last_kbd_backlight: Rc<RefCell<HashMap<String, i32>>>,
+	auto_brightness: Rc<Cell<AutoBrightnessState>>,
+	/// The previous action, when it ran, and how many times it was repeated
+	last_action: Rc<RefCell<Option<(ArgTypes, Instant, usize)>>>,
 }
 
 /// Iterate the "correct" monitors
@@ -129,26 +207,93 @@ impl SwayOSDApplication {
 		if let Some(max_volume) = server_config.max_volume {
 			action_options.max_volume.set_default(max_volume);
 		}
+		if let Some(device_volume_limits) = &server_config.device_volume_limits {
+			action_options
+				.device_volume_limits
+				.set_default(device_volume_limits.clone());
+		}
 		if let Some(min_brightness) = server_config.min_brightness {
 			action_options.min_brightness.set_default(min_brightness)
 		}
+		if let Some(exponent) = server_config.brightness_exponent
+			&& exponent > 0.0
+		{
+			action_options.brightness_exponent.set_default(exponent);
+		}
+		if let Some(fade_duration) = server_config.brightness_fade_duration {
+			action_options
+				.brightness_fade_duration
+				.set_default(fade_duration);
+		}
+		if let Some(presets) = &server_config.brightness_presets
+			&& !presets.is_empty()
+		{
+			let mut presets: Vec<u32> = presets.iter().map(|preset| (*preset).min(100)).collect();
+			presets.sort_unstable();
+			presets.dedup();
+			action_options.brightness_presets.set_default(presets);
+		}
+		if let Some(brightness_backends) = &server_config.brightness_backend {
+			action_options
+				.brightness_backends
+				.set_default(brightness_backends.clone());
+		}
+		if let Some(monitor_backlights) = &server_config.monitor_backlights {
+			action_options
+				.monitor_backlights
+				.set_default(monitor_backlights.clone());
+		}
 		if let Some(show_percentage) = server_config.show_percentage {
 			action_options.show_percentage.set_default(show_percentage);
 		}
 		if let Some(duration) = server_config.duration {
 			action_options.duration.set_default(duration);
 		}
+		if let Some(volume_scale) = server_config.volume_scale {
+			action_options.volume_scale.set_default(volume_scale);
+		}
+		if let Some(snap_to_step) = server_config.snap_to_step {
+			action_options.snap_to_step.set_default(snap_to_step);
+		}
+		if let Some(volume_barrier) = server_config.volume_barrier {
+			action_options.volume_barrier.set_default(volume_barrier);
+		}
+		if let Some(volume_barrier_pause) = server_config.volume_barrier_pause {
+			action_options
+				.volume_barrier_pause
+				.set_default(volume_barrier_pause);
+		}
 
 		Self::parse_args(&args, &mut action_options);
 
 		let osd_app = SwayOSDApplication {
 			app: app.clone(),
 			windows: Rc::new(RefCell::new(Vec::new())),
+			indicator_windows: Rc::new(RefCell::new(Vec::new())),
+			indicators: Rc::new(RefCell::new(BTreeMap::new())),
 			activated: Rc::new(RefCell::new(false)),
 			_hold: hold,
 			action_options: Rc::new(action_options),
 
-			volume_ctrl: Rc::new(RefCell::new(None)),
+			feedback_sound: server_config.feedback_sound.clone(),
+			volume_backend: Rc::new(RefCell::new(VolumeConnection::new(
+				server_config.volume_backend,
+			))),
+			volume_reconnect: Rc::new(RefCell::new(None)),
+			system_bus: Rc::new(RefCell::new(None)),
+			bluez: Rc::new(RefCell::new(None)),
+			last_volume_raise: Rc::new(RefCell::new(HashMap::new())),
+			action_count: Rc::new(Cell::new(0)),
+			brightness_backend_cache: Rc::new(RefCell::new(BrightnessBackendCache::default())),
+			brightness_fade: Rc::new(RefCell::new(None)),
+			last_brightness_change: Rc::new(Cell::new(None)),
+			last_kbd_backlight: Rc::new(RefCell::new(HashMap::new())),
+			auto_brightness: Rc::new(Cell::new(AutoBrightnessState {
+				enabled: server_config.auto_brightness.unwrap_or(false)
+					&& server_config.auto_brightness_active.unwrap_or(true),
+				..Default::default()
+			})),
+			last_action: Rc::new(RefCell::new(None)),
 		};
 
 		// Listen for any actions sent from swayosd-client
@@ -176,7 +321,105 @@ impl SwayOSDApplication {
 				osd_app,
 				#[strong]
 				server_config,
-				async move { osd_app.listen_to_upower_kbd_backlight(&server_config).await }
+				async move {
+					if let Err(error) = osd_app
+						.listen_to_upower_kbd_backlights(&server_config)
+						.await
+					{
+						eprintln!(
+							"Could not listen to the UPower keyboard backlights: {}",
+							error
+						)
+					}
+				}
+			));
+		}
+
+		// Listen for mute changes of the default source
+		if let Some(mode) = server_config.mic_mute_indicator {
+			let (sender, receiver) = async_channel::bounded::<bool>(1);
+			pulseaudio::spawn_listener(
+				InterestMaskSet::SOURCE | InterestMaskSet::SERVER,
+				sender,
+				|ctrl| Ok(ctrl.get_default_device(DeviceKind::Source)?.mute),
+			);
+			MainContext::default().spawn_local(clone!(
+				#[strong]
+				osd_app,
+				async move {
+					while let Ok(muted) = receiver.recv().await {
+						let icon_name = match (mode, muted) {
+							(MicMuteIndicator::Muted, true) => Some("source-volume-muted-symbolic"),
+							(MicMuteIndicator::Unmuted, false) => {
+								Some("source-volume-high-symbolic")
+							}
+							_ => None,
+						};
+						osd_app.set_indicator(
+							"mic-mute",
+							icon_name.map(|icon_name| Indicator {
+								icon_name: icon_name.to_string(),
+								label: None,
+							}),
+						);
+					}
+					Break
+				}
+			));
+		}
+
+		// Listen for applications recording from any source
+		if server_config.mic_in_use_indicator.unwrap_or(false) {
+			let (sender, receiver) = async_channel::bounded::<Vec<String>>(1);
+			pulseaudio::spawn_listener(
+				InterestMaskSet::SOURCE_OUTPUT | InterestMaskSet::SOURCE,
+				sender,
+				|ctrl| Ok(ctrl.get_recording_applications()?),
+			);
+			MainContext::default().spawn_local(clone!(
+				#[strong]
+				osd_app,
+				async move {
+					while let Ok(applications) = receiver.recv().await {
+						let indicator = (!applications.is_empty()).then(|| Indicator {
+							icon_name: "audio-input-microphone-symbolic".to_string(),
+							label: Some(applications.join(", ")),
+						});
+						osd_app.set_indicator("mic-in-use", indicator);
+					}
+					Break
+				}
+			));
+		}
+
+		// Listen for backlight changes not made by SwayOSD
+		if server_config.watch_backlight.unwrap_or(false) {
+			let (sender, receiver) = async_channel::bounded::<String>(1);
+			brightness_backend::spawn_backlight_watcher(sender);
+			MainContext::default().spawn_local(clone!(
+				#[strong]
+				osd_app,
+				async move {
+					while let Ok(device_name) = receiver.recv().await {
+						osd_app.external_brightness_changed(device_name);
+					}
+					Break
+				}
+			));
+		}
+
+		// Adjust the brightness to the ambient light
+		if server_config.auto_brightness.unwrap_or(false) {
+			MainContext::default().spawn_local(clone!(
+				#[strong]
+				osd_app,
+				#[strong]
+				server_config,
+				async move {
+					if let Err(error) = osd_app.listen_to_ambient_light(&server_config).await {
+						eprintln!("Could not listen to the ambient light sensor: {}", error)
+					}
+				}
 			));
 		}
 
@@ -200,6 +443,12 @@ impl SwayOSDApplication {
 							Some(evdev_rs::enums::EV_KEY::KEY_SCROLLLOCK) => {
 								(ArgTypes::ScrollLock, Some(state.to_string()))
 							}
+							Some(evdev_rs::enums::EV_KEY::KEY_BRIGHTNESS_AUTO) => {
+								(ArgTypes::BrightnessAutoToggle, None)
+							}
+							Some(evdev_rs::enums::EV_KEY::KEY_BRIGHTNESS_CYCLE) => {
+								(ArgTypes::BrightnessCycle, None)
+							}
 							_ => continue,
 						};
 					if let Err(error) =
@@ -248,12 +497,42 @@ impl SwayOSDApplication {
 		}
 	}
 
+	async fn listen_to_upower_kbd_backlights(
+		&self,
+		server_config: &Arc<ServerConfig>,
+	) -> zbus::Result<()> {
+		let backlights = upower::KbdBacklight::init_all(&self.get_system_bus().await?).await?;
+		// Only show which keyboard changed when there's more than one
+		let show_name = backlights.len() > 1;
+		for (name, proxy) in backlights {
+			MainContext::default().spawn_local(clone!(
+				#[strong(rename_to = osd_app)]
+				self,
+				#[strong]
+				server_config,
+				async move {
+					let name = show_name.then_some(name);
+					if let Err(error) = osd_app
+						.listen_to_upower_kbd_backlight(&server_config, proxy, name)
+						.await
+					{
+						eprintln!(
+							"Could not listen to the UPower keyboard backlight: {}",
+							error
+						)
+					}
+				}
+			));
+		}
+		Ok(())
+	}
+
 	async fn listen_to_upower_kbd_backlight(
 		&self,
 		server_config: &Arc<ServerConfig>,
+		proxy: upower::KbdBacklightProxy<'_>,
+		name: Option<String>,
 	) -> zbus::Result<ControlFlow> {
-		// TODO: Support other UPower KdbBacklights with version 1.91
-		let proxy = upower::KbdBacklight::init().await?;
 		let max_brightness = proxy.get_max_brightness().await?;
 		let mut changed_stream = proxy.receive_brightness_changed_with_source().await?;
 		while let Some(msg) = changed_stream.next().await {
@@ -263,11 +542,14 @@ impl SwayOSDApplication {
 					// (automatically or through a firmware-handled hotkey being pressed)
 					continue;
 				}
+				let flags = name
+					.clone()
+					.map(|name| vec![(ArgFlags::DeviceName, Some(name))]);
 				if let Err(error) = self.action_activated(
 					server_config.clone(),
 					ArgTypes::KbdBacklight,
 					Some(format!("{}:{}", args.value, max_brightness)),
-					None,
+					flags,
 				) {
 					eprintln!("Could not activate action: {:?}", error)
 				}
@@ -279,6 +561,59 @@ impl SwayOSDApplication {
 		zbus::Result::Ok(Break)
 	}
 
+	/// Changes the keyboard backlight through UPower without blocking
+	fn adjust_kbd_backlight(
+		&self,
+		action_options: ActionOptions,
+		change_type: KbdBacklightChangeType,
+		value: Option<String>,
+	) {
+		MainContext::default().spawn_local(clone!(
+			#[strong(rename_to = osd_app)]
+			self,
+			async move {
+				if let Err(error) = osd_app
+					.set_kbd_backlight(&action_options, change_type, value)
+					.await
+				{
+					eprintln!("Could not change the keyboard backlight: {:?}", error)
+				}
+			}
+		));
+	}
+
+	async fn set_kbd_backlight(
+		&self,
+		action_options: &ActionOptions,
+		change_type: KbdBacklightChangeType,
+		value: Option<String>,
+	) -> zbus::Result<()> {
+		let (name, proxy) = upower::KbdBacklight::init(
+			&self.get_system_bus().await?,
+			action_options.device_name.get().as_deref(),
+		)
+		.await?;
+		let max = proxy.get_max_brightness().await?;
+		let current = proxy.get_brightness().await?;
+
+		let value = value.and_then(|value| value.parse::<i32>().ok());
+		let last = self.last_kbd_backlight.borrow().get(&name).copied();
+		let target = get_kbd_backlight_target(&change_type, value, current, max, last);
+		// Restore the previous brightness when toggling back on
+		if let KbdBacklightChangeType::Toggle = change_type
+			&& current > 0
+		{
+			self.last_kbd_backlight.borrow_mut().insert(name, current);
+		}
+
+		proxy.set_brightness(target).await?;
+
+		iter_windows!(self, action_options, (window), {
+			window.changed_kbd_backlight(action_options, target as u32, max as u32);
+		});
+		Ok(())
+	}
+
 	fn libinput_backend_appeared(
 		sender: &Sender<(u16, i32)>,
 		signal_id: &Arc<Mutex<Option<SignalSubscriptionId>>>,
@@ -392,6 +727,16 @@ impl SwayOSDApplication {
 
 		let osd_app = self.clone();
 
+		// Detecting the DDC/CI monitors is slow, so start right away
+		if self
+			.action_options
+			.brightness_backends
+			.get()
+			.contains(&BrightnessBackendType::DdcUtil)
+		{
+			self.brightness_backend_cache.borrow().detect_ddc_monitors();
+		}
+
 		// Refresh the created windows if a monitor got unplugged when suspended
 		MainContext::default().spawn_local(clone!(
 			#[strong]
@@ -414,10 +759,13 @@ impl SwayOSDApplication {
 
 	fn monitors_changed(&self, monitors: &ListModel, position: u32, removed: u32, added: u32) {
 		let mut windows = self.windows.borrow_mut();
+		let mut indicator_windows = self.indicator_windows.borrow_mut();
 
 		for _ in 0..removed {
 			let window = windows.remove(position as usize);
 			window.close();
+			let window = indicator_windows.remove(position as usize);
+			window.close();
 		}
 
 		let top_margin = self.action_options.top_margin.get();
@@ -429,10 +777,72 @@ impl SwayOSDApplication {
 			{
 				let window = SwayosdWindow::new(&self.app, &monitor, top_margin);
 				windows.push(window);
+
+				let window = IndicatorWindow::new(&self.app, &monitor);
+				window.update(&self.indicators.borrow());
+				indicator_windows.push(window);
 			}
 		}
 	}
 
+	/// Shows the persistent indicator on all monitors, or hides it if None
+	fn set_indicator(&self, name: &'static str, indicator: Option<Indicator>) {
+		let mut indicators = self.indicators.borrow_mut();
+		match indicator {
+			Some(indicator) => indicators.insert(name, indicator),
+			None => indicators.remove(name),
+		};
+		for window in self.indicator_windows.borrow().iter() {
+			window.update(&indicators);
+		}
+	}
+
+	/// Returns the cached volume backend, recreating it if the connection got
+	/// lost (ex: when pipewire-pulse restarts)
+	fn get_volume_backend(
+		&self,
+	) -> Result<RefMut<'_, dyn VolumeBackend + 'static>, Box<dyn Error>> {
+		let mut connection = self.volume_backend.try_borrow_mut()?;
+		// Only try once here to not block the UI, the retries are done in the
+		// background
+		if let Err(e) = connection.get() {
+			drop(connection);
+			if self.volume_reconnect.borrow().is_none() {
+				self.schedule_volume_reconnect();
+			}
+			return Err(e.into());
+		}
+
+		RefMut::filter_map(connection, |connection| connection.get().ok())
+			.map_err(|_| "Volume backend unavailable".into())
+	}
+
+	/// Runs the pending background reconnection attempt to the volume backend
+	/// on a timer, so that the next action can use the connection right away
+	fn schedule_volume_reconnect(&self) {
+		let Some(delay) = self.volume_backend.borrow().retry_delay() else {
+			return;
+		};
+		let source_id = glib::timeout_add_local_once(
+			delay,
+			clone!(
+				#[strong(rename_to = osd_app)]
+				self,
+				move || {
+					// The source is removed after running once
+					osd_app.volume_reconnect.take();
+					let Ok(mut connection) = osd_app.volume_backend.try_borrow_mut() else {
+						return;
+					};
+					connection.retry();
+					drop(connection);
+					osd_app.schedule_volume_reconnect();
+				}
+			),
+		);
+		self.volume_reconnect.replace(Some(source_id));
+	}
+
 	fn adjust_volume(
 		&self,
 		action_options: &ActionOptions,
@@ -440,17 +850,130 @@ impl SwayOSDApplication {
 		change_type: VolumeChangeType,
 		step: Option<String>,
 	) -> Result<(), Box<dyn Error>> {
-		let max_volume: f64 = (*action_options.max_volume.get()).into();
+		let barrier_pause = Duration::from_millis(*action_options.volume_barrier_pause.get());
+
+		let is_repeat = |device: &DeviceInfo| {
+			let now = Instant::now();
+			self.last_volume_raise
+				.borrow_mut()
+				.insert((device.kind, device.index), now)
+				.is_some_and(|last| now.duration_since(last) < barrier_pause)
+		};
+
+		let play_sound = self.feedback_sound.as_ref().filter(|_| {
+			kind == DeviceKind::Sink
+				&& matches!(
+					change_type,
+					VolumeChangeType::Raise | VolumeChangeType::Lower
+				)
+		});
+
+		let mut backend = self.get_volume_backend()?;
+
+		if let Some(mut device) = change_device_volume(
+			&mut *backend,
+			kind,
+			change_type,
+			action_options,
+			is_repeat,
+			step,
+		) {
+			if let Some(path) = play_sound
+				&& !device.mute
+				&& let Err(e) = backend.play_sound_by_index(device.index, path)
+			{
+				eprintln!("Could not play the feedback sound: {}", e);
+			}
+			let action_count = self.action_count.get();
+			let mut lookup_battery = None;
+			if device.battery.is_none()
+				&& let Some(address) = device.bluetooth_address.clone()
+			{
+				let cached = self
+					.bluez
+					.borrow()
+					.as_ref()
+					.map(|bluez| bluez.get_cached_battery(&address));
+				match cached.flatten() {
+					Some(battery) => device.battery = battery,
+					None => lookup_battery = Some(address),
+				}
+			}
+			iter_windows!(self, action_options, (window), {
+				window.changed_volume(action_options, &device);
+			});
+
+			// Look up the battery level through BlueZ without blocking, and
+			// add it to the OSD unless another action ran meanwhile
+			if let Some(address) = lookup_battery {
+				let action_options = action_options.clone();
+				MainContext::default().spawn_local(clone!(
+					#[strong(rename_to = osd_app)]
+					self,
+					async move {
+						device.battery = osd_app
+							.get_bluetooth_battery(&address)
+							.await
+							.unwrap_or_else(|e| {
+								eprintln!("Could not get the Bluetooth battery level: {}", e);
+								None
+							});
+						if device.battery.is_none() || osd_app.action_count.get() != action_count {
+							return;
+						}
+						iter_windows!(osd_app, &action_options, (window), {
+							window.changed_volume(&action_options, &device);
+						});
+					}
+				));
+			}
+		}
+		Ok(())
+	}
+
+	/// Returns the system bus connection, which is shared by the D-Bus clients
+	async fn get_system_bus(&self) -> zbus::Result<zbus::Connection> {
+		let cached = self.system_bus.borrow().clone();
+		match cached {
+			Some(connection) => Ok(connection),
+			None => {
+				let connection = zbus::Connection::system().await?;
+				self.system_bus.replace(Some(connection.clone()));
+				Ok(connection)
+			}
+		}
+	}
+
+	/// Returns the battery level of the Bluetooth device through BlueZ
+	async fn get_bluetooth_battery(&self, address: &str) -> zbus::Result<Option<u8>> {
+		let cached = self.bluez.borrow().clone();
+		let bluez = match cached {
+			Some(bluez) => bluez,
+			None => {
+				let bluez = Rc::new(bluez::BlueZ::new(self.get_system_bus().await?));
+				self.bluez.replace(Some(bluez.clone()));
+				bluez
+			}
+		};
+		bluez.get_battery(address).await
+	}
+
+	fn adjust_balance(
+		&self,
+		action_options: &ActionOptions,
+		kind: DeviceKind,
+		change_type: BalanceChangeType,
+		step: Option<String>,
+	) -> Result<(), Box<dyn Error>> {
 		let device_name = action_options.device_name.get();
 
-		let mut ctrl = self.volume_ctrl.try_borrow_mut()?;
-		let ctrl = ctrl.get_or_insert(VolumeController::create()?);
+		let mut backend = self.get_volume_backend()?;
 
 		if let Some(device) =
-			change_device_volume(ctrl, kind, change_type, device_name, max_volume, step)
+			change_device_balance(&mut *backend, kind, change_type, device_name, step)
 		{
 			iter_windows!(self, action_options, (window), {
-				window.changed_volume(action_options, &device);
+				window.changed_balance(action_options, &device);
 			});
 		}
 		Ok(())
@@ -462,17 +985,336 @@ impl SwayOSDApplication {
 		change_type: BrightnessChangeType,
 		step: Option<String>,
 	) -> Result<(), Box<dyn Error>> {
-		let min_brightness = action_options.min_brightness.get();
-		let device_name = action_options.device_name.get();
+		self.cancel_brightness_fade();
+		self.last_brightness_change.set(Some(Instant::now()));
+
+		let fade_duration = *action_options.brightness_fade_duration.get();
+		if fade_duration > 0 {
+			let backends = get_brightness_backends(
+				action_options,
+				&mut self.brightness_backend_cache.borrow_mut(),
+			)?;
+			self.fade_brightness(action_options, backends, change_type, step, fade_duration);
+			return Ok(());
+		}
+
+		let mut backends = change_brightness(
+			change_type,
+			action_options,
+			&mut self.brightness_backend_cache.borrow_mut(),
+			step,
+		)?;
+		let monitor_devices = self.get_monitor_device_names(action_options, &backends);
+		self.show_brightness(action_options, &mut backends, &monitor_devices);
+		Ok(())
+	}
 
-		let mut brightness_backend =
-			change_brightness(change_type, device_name, *min_brightness, step)?;
+	/// Changes the brightness to the next preset, or back to the first one
+	/// after the last preset
+	fn cycle_brightness(&self, action_options: &ActionOptions) -> Result<(), Box<dyn Error>> {
+		let presets = action_options.brightness_presets.get();
+		let exponent = *action_options.brightness_exponent.get();
+
+		let mut backends = get_brightness_backends(
+			action_options,
+			&mut self.brightness_backend_cache.borrow_mut(),
+		)?;
+		let current = match backends.first_mut() {
+			Some((_, backend)) => {
+				brightness_to_percent(backend.get_current(), backend.get_max(), exponent)
+			}
+			None => return Ok(()),
+		};
+		let Some(index) = get_next_brightness_preset(presets, current) else {
+			return Ok(());
+		};
+		let preset = presets[index];
+
+		self.cancel_brightness_fade();
+		self.last_brightness_change.set(Some(Instant::now()));
+
+		// Change the backends that the current brightness was read from
+		change_brightness_backends(
+			&mut backends,
+			BrightnessChangeType::Set,
+			action_options,
+			Some(preset.to_string()),
+		)?;
 		iter_windows!(self, action_options, (window), {
-			window.changed_brightness(action_options, brightness_backend.as_mut());
+			window.changed_brightness_preset(
+				action_options,
+				index as u32 + 1,
+				presets.len() as u32,
+				preset,
+			);
 		});
 		Ok(())
 	}
 
+	/// Stops the running brightness fade where it currently is
+	fn cancel_brightness_fade(&self) {
+		if let Some(source_id) = self.brightness_fade.take() {
+			source_id.remove();
+		}
+	}
+
+	/// Gradually changes the brightness of the devices to the target over the
+	/// duration, in steps of the perceived brightness. Devices that don't
+	/// support fading are changed to the target right away
+	fn fade_brightness(
+		&self,
+		action_options: &ActionOptions,
+		mut backends: BrightnessBackends,
+		change_type: BrightnessChangeType,
+		step: Option<String>,
+		duration: u64,
+	) {
+		let exponent = *action_options.brightness_exponent.get();
+		// The perceived start and target brightness of each device that fades
+		let mut fades: Vec<Option<(f64, f64)>> = Vec::new();
+		backends.retain_mut(|(name, backend)| {
+			let target =
+				get_target_brightness(backend.as_mut(), &change_type, action_options, &step);
+			match target {
+				Ok(target) if backend.supports_fading() => {
+					let max = backend.get_max();
+					fades.push(Some((
+						brightness_to_percent(backend.get_current(), max, exponent),
+						brightness_to_percent(target, max, exponent),
+					)));
+					true
+				}
+				Ok(target) => match backend.set_raw(target) {
+					Ok(()) => {
+						fades.push(None);
+						true
+					}
+					Err(e) => {
+						eprintln!(
+							"Could not change the brightness of {}: {}",
+							name.as_deref().unwrap_or_default(),
+							e
+						);
+						false
+					}
+				},
+				Err(e) => {
+					eprintln!(
+						"Could not change the brightness of {}: {}",
+						name.as_deref().unwrap_or_default(),
+						e
+					);
+					false
+				}
+			}
+		});
+
+		let monitor_devices = self.get_monitor_device_names(action_options, &backends);
+		let n_steps = (duration / BRIGHTNESS_FADE_INTERVAL).max(1);
+		let mut current_step = 0;
+		let action_options = action_options.clone();
+		let source_id = glib::timeout_add_local(
+			Duration::from_millis(BRIGHTNESS_FADE_INTERVAL),
+			clone!(
+				#[strong(rename_to = osd_app)]
+				self,
+				move || {
+					current_step += 1;
+					let progress = current_step as f64 / n_steps as f64;
+					for ((_, backend), fade) in backends.iter_mut().zip(&fades) {
+						let Some((start, target)) = fade else {
+							continue;
+						};
+						let max = backend.get_max();
+						let value = start + (target - start) * progress;
+						let value = brightness_from_percent(value, max, exponent);
+						if let Err(e) = backend.set_raw(value) {
+							eprintln!("Could not change the brightness: {}", e);
+						}
+					}
+					osd_app.show_brightness(&action_options, &mut backends, &monitor_devices);
+					osd_app.last_brightness_change.set(Some(Instant::now()));
+
+					if current_step < n_steps {
+						return ControlFlow::Continue;
+					}
+					// The source is removed when returning Break
+					osd_app.brightness_fade.take();
+					Break
+				}
+			),
+		);
+		self.brightness_fade.replace(Some(source_id));
+	}
+
+	/// Shows the OSD when the backlight was changed by something else than us,
+	/// ex: the firmware or an ambient light daemon
+	fn external_brightness_changed(&self, device_name: String) {
+		let ignore_timeout = Duration::from_millis(BRIGHTNESS_CHANGE_IGNORE_TIMEOUT);
+		if self.brightness_fade.borrow().is_some()
+			|| self
+				.last_brightness_change
+				.get()
+				.is_some_and(|last| last.elapsed() < ignore_timeout)
+		{
+			return;
+		}
+
+		let action_options = &self.action_options;
+		let backend = self
+			.brightness_backend_cache
+			.borrow_mut()
+			.get_preferred_backend(
+				Some(device_name.clone()),
+				action_options.brightness_backends.get(),
+			);
+		match backend {
+			Ok(backend) => {
+				self.show_brightness(
+					action_options,
+					&mut vec![(Some(device_name), backend)],
+					&HashMap::new(),
+				);
+			}
+			Err(e) => eprintln!("Could not show the brightness of {}: {}", device_name, e),
+		}
+	}
+
+	async fn listen_to_ambient_light(
+		&self,
+		server_config: &Arc<ServerConfig>,
+	) -> zbus::Result<ControlFlow> {
+		// The light level is only updated while claimed, which lasts until we exit
+		let (proxy, unit) = sensor_proxy::SensorProxy::init(&self.get_system_bus().await?).await?;
+		let mut state = self.auto_brightness.get();
+		state.unit = unit;
+		self.auto_brightness.set(state);
+
+		self.ambient_light_changed(server_config, proxy.light_level().await?);
+
+		let mut changed_stream = proxy.receive_light_level_changed().await;
+		while let Some(msg) = changed_stream.next().await {
+			match msg.get().await {
+				Ok(level) => self.ambient_light_changed(server_config, level),
+				Err(error) => eprintln!("Ambient light level isn't valid: {}", error),
+			}
+		}
+		eprintln!("Ambient light stream ended unexpectedly");
+		zbus::Result::Ok(Break)
+	}
+
+	fn ambient_light_changed(&self, server_config: &ServerConfig, level: f64) {
+		let mut state = self.auto_brightness.get();
+		state.light_level = Some(level);
+		state.highest_light_level = state.highest_light_level.max(level);
+		self.auto_brightness.set(state);
+		self.apply_auto_brightness(server_config);
+	}
+
+	/// Changes the brightness to match the ambient light, if enabled
+	fn apply_auto_brightness(&self, server_config: &ServerConfig) {
+		let mut state = self.auto_brightness.get();
+		// Don't interfere with a brightness change that's in progress
+		if !state.enabled || self.brightness_fade.borrow().is_some() {
+			return;
+		}
+
+		let curve = server_config
+			.auto_brightness_curve
+			.as_deref()
+			.unwrap_or(DEFAULT_AUTO_BRIGHTNESS_CURVE);
+		let Some(brightness) = auto_brightness_from_light_level(curve, &state) else {
+			return;
+		};
+		if state
+			.brightness
+			.is_some_and(|last| (last - brightness).abs() < AUTO_BRIGHTNESS_THRESHOLD)
+		{
+			return;
+		}
+		state.brightness = Some(brightness);
+		self.auto_brightness.set(state);
+
+		// Don't show the OSD for our own change
+		self.last_brightness_change.set(Some(Instant::now()));
+		let value = (brightness.round() as u32).to_string();
+		if let Err(e) = change_brightness(
+			BrightnessChangeType::Set,
+			&self.action_options,
+			&mut self.brightness_backend_cache.borrow_mut(),
+			Some(value),
+		) {
+			eprintln!(
+				"Could not change the brightness to the ambient light: {}",
+				e
+			);
+		}
+	}
+
+	fn toggle_auto_brightness(&self, server_config: &ServerConfig, action_options: &ActionOptions) {
+		if !server_config.auto_brightness.unwrap_or(false) {
+			eprintln!("Auto brightness isn't enabled in the config");
+			return;
+		}
+
+		let mut state = self.auto_brightness.get();
+		state.enabled = !state.enabled;
+		// Always apply the ambient light brightness when enabled again
+		state.brightness = None;
+		self.auto_brightness.set(state);
+
+		iter_windows!(self, action_options, (window), {
+			window.changed_auto_brightness(action_options, state.enabled);
+		});
+		self.apply_auto_brightness(server_config);
+	}
+
+	/// Returns the brightness device of each monitor by connector, which is
+	/// only needed when changing multiple devices
+	fn get_monitor_device_names(
+		&self,
+		action_options: &ActionOptions,
+		backends: &BrightnessBackends,
+	) -> HashMap<String, Option<String>> {
+		if backends.len() < 2 {
+			return HashMap::new();
+		}
+		self.windows
+			.borrow()
+			.iter()
+			.filter_map(|window| window.monitor.connector())
+			.map(|connector| {
+				let device_name = get_monitor_device_name(action_options, &connector);
+				(connector.to_string(), device_name)
+			})
+			.collect()
+	}
+
+	/// `monitor_devices` is the brightness device of each monitor, from
+	/// get_monitor_device_names()
+	fn show_brightness(
+		&self,
+		action_options: &ActionOptions,
+		backends: &mut BrightnessBackends,
+		monitor_devices: &HashMap<String, Option<String>>,
+	) {
+		iter_windows!(self, action_options, (window), {
+			// Show the brightness of the monitor's own device when changing multiple devices
+			let device_name = window
+				.monitor
+				.connector()
+				.and_then(|connector| monitor_devices.get(connector.as_str()).cloned())
+				.flatten();
+			let index = backends
+				.iter()
+				.position(|(name, _)| name.is_some() && *name == device_name)
+				.unwrap_or(0);
+			if let Some((_, backend)) = backends.get_mut(index) {
+				window.changed_brightness(action_options, backend.as_mut());
+			}
+		});
+	}
+
 	fn adjust_keylock(
 		&self,
 		action_options: &ActionOptions,
@@ -490,6 +1332,23 @@ impl SwayOSDApplication {
 		Ok(())
 	}
 
+	/// Returns how many times in a row the action has been repeated, where each
+	/// repeat has to arrive within the timeout of the previous one
+	fn count_repeats(&self, arg_type: &ArgTypes, timeout: Duration) -> usize {
+		let now = Instant::now();
+		let mut last_action = self.last_action.borrow_mut();
+		let repeats = match last_action.as_ref() {
+			Some((last_type, last_time, repeats))
+				if last_type == arg_type && now.duration_since(*last_time) < timeout =>
+			{
+				repeats + 1
+			}
+			_ => 0,
+		};
+		last_action.replace((arg_type.clone(), now, repeats));
+		repeats
+	}
+
 	fn action_activated(
 		&self,
 		server_config: Arc<ServerConfig>,
@@ -498,6 +1357,7 @@ impl SwayOSDApplication {
 		flags: Option<DbusSenderFlagsType>,
 	) -> Result<(), Box<dyn Error>> {
 		let mut action_options: ActionOptions = (*self.action_options).clone();
+		self.action_count.set(self.action_count.get() + 1);
 
 		// Parse flags
 		for (flag, value) in flags.unwrap_or_default() {
@@ -527,6 +1387,9 @@ impl SwayOSDApplication {
 				(ArgFlags::CustomIcon, icon) => {
 					action_options.icon_name.set(icon);
 				}
+				(ArgFlags::IgnoreVolumeBarrier, _) => {
+					action_options.volume_barrier.set(Some(false));
+				}
 				(ArgFlags::Duration, duration) => {
 					let duration: Option<u64> = duration.and_then(|d| d.parse().ok());
 					action_options.duration.set(duration);
@@ -534,6 +1397,29 @@ impl SwayOSDApplication {
 			};
 		}
 
+		// Accelerate the step when the same action is quickly repeated
+		let acceleration_timeout = Duration::from_millis(
+			server_config
+				.acceleration_timeout
+				.unwrap_or(DEFAULT_ACCELERATION_TIMEOUT),
+		);
+		let repeats = self.count_repeats(&arg_type, acceleration_timeout);
+		let acceleration = match arg_type {
+			ArgTypes::SinkVolumeRaise
+			| ArgTypes::SinkVolumeLower
+			| ArgTypes::SourceVolumeRaise
+			| ArgTypes::SourceVolumeLower => server_config.volume_acceleration.as_deref(),
+			ArgTypes::BrightnessRaise | ArgTypes::BrightnessLower => {
+				server_config.brightness_acceleration.as_deref()
+			}
+			_ => None,
+		};
+		if let Some(multiplier) =
+			acceleration.and_then(|acceleration| acceleration.get(repeats).or(acceleration.last()))
+		{
+			action_options.step_multiplier.set(Some(*multiplier));
+		}
+
 		// Execute the action
 		match (arg_type, value) {
 			// Pulse Sink
@@ -568,6 +1454,26 @@ impl SwayOSDApplication {
 				None,
 			)?,
 
+			// Pulse Sink balance
+			(ArgTypes::SinkBalanceLeft, step) => self.adjust_balance(
+				&action_options,
+				DeviceKind::Sink,
+				BalanceChangeType::Left,
+				step,
+			)?,
+			(ArgTypes::SinkBalanceRight, step) => self.adjust_balance(
+				&action_options,
+				DeviceKind::Sink,
+				BalanceChangeType::Right,
+				step,
+			)?,
+			(ArgTypes::SinkBalanceCenter, _) => self.adjust_balance(
+				&action_options,
+				DeviceKind::Sink,
+				BalanceChangeType::Center,
+				None,
+			)?,
+
 			// Pulse Source
 			(ArgTypes::SourceVolumeRaise, step) => self.adjust_volume(
 				&action_options,
@@ -610,6 +1516,10 @@ impl SwayOSDApplication {
 			(ArgTypes::BrightnessSet, value) => {
 				self.adjust_brightness(&action_options, BrightnessChangeType::Set, value)?
 			}
+			(ArgTypes::BrightnessAutoToggle, _) => {
+				self.toggle_auto_brightness(&server_config, &action_options)
+			}
+			(ArgTypes::BrightnessCycle, _) => self.cycle_brightness(&action_options)?,
 
 			// Keystates
 			(ArgTypes::CapsLock, value) => {
@@ -657,6 +1567,19 @@ impl SwayOSDApplication {
 				}
 			}
 
+			(ArgTypes::KbdBacklightRaise, step) => {
+				self.adjust_kbd_backlight(action_options, KbdBacklightChangeType::Raise, step)
+			}
+			(ArgTypes::KbdBacklightLower, step) => {
+				self.adjust_kbd_backlight(action_options, KbdBacklightChangeType::Lower, step)
+			}
+			(ArgTypes::KbdBacklightToggle, _) => {
+				self.adjust_kbd_backlight(action_options, KbdBacklightChangeType::Toggle, None)
+			}
+			(ArgTypes::KbdBacklightSet, value) => {
+				self.adjust_kbd_backlight(action_options, KbdBacklightChangeType::Set, value)
+			}
+
 			// Custom actions
 			(ArgTypes::CustomMessage, message) => {
 				if let Some(message) = message {
@@ -0,0 +1,221 @@
+use std::{cell::RefCell, collections::HashMap};
+
+use zbus::{fdo::ObjectManagerProxy, proxy, zvariant::OwnedObjectPath, Connection};
+
+const DEVICE_INTERFACE: &str = "org.bluez.Device1";
+const BATTERY_INTERFACE: &str = "org.bluez.Battery1";
+
+#[proxy(default_service = "org.bluez", interface = "org.bluez.Battery1")]
+pub trait Battery1 {
+	#[zbus(property)]
+	fn percentage(&self) -> zbus::Result<u8>;
+}
+
+/// Looks up the battery level of Bluetooth devices. The battery proxy of each
+/// device is kept, so that its cached percentage can be used afterwards.
+pub struct BlueZ {
+	connection: Connection,
+	/// The battery proxies by device address, or None for devices without a
+	/// battery, so that they aren't looked up again
+	batteries: RefCell<HashMap<String, Option<Battery1Proxy<'static>>>>,
+}
+
+impl BlueZ {
+	pub fn new(connection: Connection) -> Self {
+		Self {
+			connection,
+			batteries: RefCell::new(HashMap::new()),
+		}
+	}
+
+	/// Returns the object path of the BlueZ device with the given address,
+	/// if it reports its battery level
+	async fn find_battery_path(&self, address: &str) -> zbus::Result<Option<OwnedObjectPath>> {
+		let proxy = ObjectManagerProxy::builder(&self.connection)
+			.destination("org.bluez")?
+			.path("/")?
+			.build()
+			.await?;
+
+		for (path, interfaces) in proxy.get_managed_objects().await? {
+			let is_device = interfaces
+				.iter()
+				.find(|(name, _)| name.as_str() == DEVICE_INTERFACE)
+				.and_then(|(_, props)| props.get("Address"))
+				.and_then(|value| <&str>::try_from(value).ok())
+				.is_some_and(|value| value.eq_ignore_ascii_case(address));
+			if is_device {
+				let has_battery = interfaces
+					.keys()
+					.any(|name| name.as_str() == BATTERY_INTERFACE);
+				return Ok(has_battery.then_some(path));
+			}
+		}
+		Ok(None)
+	}
+
+	/// Returns the battery percentage of the device without any D-Bus calls,
+	/// or None if it isn't known yet
+	pub fn get_cached_battery(&self, address: &str) -> Option<Option<u8>> {
+		match self.batteries.borrow().get(address)? {
+			Some(proxy) => proxy.cached_percentage().ok().flatten().map(Some),
+			None => Some(None),
+		}
+	}
+
+	/// Returns the battery percentage of the BlueZ device with the given
+	/// address, if it reports one
+	pub async fn get_battery(&self, address: &str) -> zbus::Result<Option<u8>> {
+		let cached = self.batteries.borrow().get(address).cloned();
+		let proxy = match cached {
+			Some(Some(proxy)) => proxy,
+			Some(None) => return Ok(None),
+			None => {
+				let Some(path) = self.find_battery_path(address).await? else {
+					self.batteries
+						.borrow_mut()
+						.insert(address.to_string(), None);
+					return Ok(None);
+				};
+				let proxy = Battery1Proxy::builder(&self.connection)
+					.path(path)?
+					.build()
+					.await?;
+				self.batteries
+					.borrow_mut()
+					.insert(address.to_string(), Some(proxy.clone()));
+				proxy
+			}
+		};
+
+		match proxy.percentage().await {
+			Ok(percentage) => Ok(Some(percentage)),
+			Err(e) => {
+				// The device might be gone, look it up again next time
+				self.batteries.borrow_mut().remove(address);
+				Err(e)
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_utils::mock_connection;
+	use std::time::Duration;
+	use zbus::{fdo::ObjectManager, interface};
+
+	const DEVICE_PATH: &str = "/org/bluez/hci0/dev_AA_BB_CC_DD_EE_FF";
+	const NO_BATTERY_PATH: &str = "/org/bluez/hci0/dev_11_22_33_44_55_66";
+
+	struct MockDevice {
+		address: &'static str,
+	}
+
+	#[interface(name = "org.bluez.Device1")]
+	impl MockDevice {
+		#[zbus(property)]
+		fn address(&self) -> String {
+			self.address.to_string()
+		}
+	}
+
+	struct MockBattery {
+		percentage: u8,
+	}
+
+	#[interface(name = "org.bluez.Battery1")]
+	impl MockBattery {
+		#[zbus(property)]
+		fn percentage(&self) -> u8 {
+			self.percentage
+		}
+	}
+
+	async fn mock_bluez() -> zbus::Result<(Connection, BlueZ)> {
+		let (server, client) = mock_connection(|builder| {
+			builder
+				.serve_at("/", ObjectManager)?
+				.serve_at(
+					DEVICE_PATH,
+					MockDevice {
+						address: "AA:BB:CC:DD:EE:FF",
+					},
+				)?
+				.serve_at(DEVICE_PATH, MockBattery { percentage: 80 })?
+				.serve_at(
+					NO_BATTERY_PATH,
+					MockDevice {
+						address: "11:22:33:44:55:66",
+					},
+				)
+		})
+		.await?;
+		Ok((server, BlueZ::new(client)))
+	}
+
+	#[test]
+	fn gets_battery_by_address() {
+		async_std::task::block_on(async {
+			let (_server, bluez) = mock_bluez().await.unwrap();
+			assert_eq!(bluez.get_battery("aa:bb:cc:dd:ee:ff").await, Ok(Some(80)));
+			assert_eq!(bluez.get_battery("11:22:33:44:55:66").await, Ok(None));
+			assert_eq!(bluez.get_battery("00:00:00:00:00:00").await, Ok(None));
+		});
+	}
+
+	#[test]
+	fn caches_battery_lookups() {
+		async_std::task::block_on(async {
+			let (server, bluez) = mock_bluez().await.unwrap();
+			assert_eq!(bluez.get_cached_battery("AA:BB:CC:DD:EE:FF"), None);
+			assert_eq!(bluez.get_cached_battery("11:22:33:44:55:66"), None);
+
+			assert_eq!(bluez.get_battery("AA:BB:CC:DD:EE:FF").await, Ok(Some(80)));
+			assert_eq!(bluez.get_battery("11:22:33:44:55:66").await, Ok(None));
+			assert_eq!(
+				bluez.get_cached_battery("AA:BB:CC:DD:EE:FF"),
+				Some(Some(80))
+			);
+			assert_eq!(bluez.get_cached_battery("11:22:33:44:55:66"), Some(None));
+
+			// Devices without a battery aren't looked up again
+			server
+				.object_server()
+				.at(NO_BATTERY_PATH, MockBattery { percentage: 30 })
+				.await
+				.unwrap();
+			assert_eq!(bluez.get_battery("11:22:33:44:55:66").await, Ok(None));
+		});
+	}
+
+	#[test]
+	fn follows_battery_changes() {
+		async_std::task::block_on(async {
+			let (server, bluez) = mock_bluez().await.unwrap();
+			assert_eq!(bluez.get_battery("AA:BB:CC:DD:EE:FF").await, Ok(Some(80)));
+
+			let battery = server
+				.object_server()
+				.interface::<_, MockBattery>(DEVICE_PATH)
+				.await
+				.unwrap();
+			battery.get_mut().await.percentage = 50;
+			battery
+				.get()
+				.await
+				.percentage_changed(battery.signal_emitter())
+				.await
+				.unwrap();
+
+			for _ in 0..100 {
+				if bluez.get_battery("AA:BB:CC:DD:EE:FF").await == Ok(Some(50)) {
+					return;
+				}
+				async_std::task::sleep(Duration::from_millis(10)).await;
+			}
+			panic!("The battery level wasn't updated");
+		});
+	}
+}
@@ -0,0 +1,89 @@
+use gtk::{gdk, prelude::*};
+use gtk_layer_shell::LayerShell;
+use std::collections::BTreeMap;
+
+const ICON_SIZE: i32 = 24;
+const MARGIN: i32 = 12;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Indicator {
+	pub icon_name: String,
+	pub label: Option<String>,
+}
+
+/// A small window that stays visible for as long as it has any indicators,
+/// used for persistent states like a muted microphone. Anchored to the top
+/// right corner of the monitor.
+#[derive(Clone, Debug)]
+pub struct IndicatorWindow {
+	window: gtk::ApplicationWindow,
+	container: gtk::Box,
+}
+
+impl IndicatorWindow {
+	pub fn new(app: &gtk::Application, monitor: &gdk::Monitor) -> Self {
+		let window = gtk::ApplicationWindow::new(app);
+		window.set_widget_name("osd");
+		window.add_css_class("osd");
+		window.add_css_class("indicator");
+
+		window.init_layer_shell();
+		window.set_monitor(Some(monitor));
+		window.set_namespace(Some("swayosd-indicator"));
+
+		window.set_layer(gtk_layer_shell::Layer::Overlay);
+		window.set_anchor(gtk_layer_shell::Edge::Top, true);
+		window.set_anchor(gtk_layer_shell::Edge::Right, true);
+		window.set_margin(gtk_layer_shell::Edge::Top, MARGIN);
+		window.set_margin(gtk_layer_shell::Edge::Right, MARGIN);
+
+		let container = cascade! {
+			gtk::Box::new(gtk::Orientation::Horizontal, 8);
+			..set_widget_name("container");
+		};
+
+		window.set_child(Some(&container));
+
+		// Disable mouse input
+		window.connect_map(|window| {
+			if let Some(surface) = window.surface() {
+				let region = gtk::cairo::Region::create();
+				surface.set_input_region(&region);
+			}
+		});
+
+		Self { window, container }
+	}
+
+	/// Replaces the shown indicators. Each key is added as a CSS class to its
+	/// widgets, and the window is hidden when there are no indicators.
+	pub fn update(&self, indicators: &BTreeMap<&'static str, Indicator>) {
+		let mut next = self.container.first_child();
+		while let Some(widget) = next {
+			next = widget.next_sibling();
+			self.container.remove(&widget);
+		}
+
+		for (name, indicator) in indicators {
+			let icon =
+				gtk::gio::ThemedIcon::from_names(&[&indicator.icon_name, "missing-symbolic"]);
+			self.container.append(&cascade! {
+				gtk::Image::from_gicon(&icon.upcast::<gtk::gio::Icon>());
+				..set_pixel_size(ICON_SIZE);
+				..add_css_class(name);
+			});
+			if let Some(label) = &indicator.label {
+				self.container.append(&cascade! {
+					gtk::Label::new(Some(label));
+					..add_css_class(name);
+				});
+			}
+		}
+
+		self.window.set_visible(!indicators.is_empty());
+	}
+
+	pub fn close(&self) {
+		self.window.close();
+	}
+}
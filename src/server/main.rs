@@ -1,6 +1,11 @@
 mod application;
+mod bluez;
+mod indicator_window;
 mod login1;
 mod osd_window;
+mod sensor_proxy;
+#[cfg(test)]
+mod test_utils;
 mod upower;
 mod utils;
 mod widgets;
@@ -8,17 +8,23 @@ use std::cell::RefCell;
 use std::rc::Rc;
 use std::time::Duration;
 
-use crate::utils::{volume_to_f64, KeysLocks};
+use crate::utils::{
+	balance_to_f64, brightness_to_percent, format_volume, get_max_volume, volume_to_f64,
+	volume_to_fraction, KeysLocks,
+};
 use crate::widgets::segmented_progress_widget::SegmentedProgressWidget;
 use crate::{
 	actions::{
 		brightness_backend::BrightnessBackend,
-		pulse::{DeviceInfo, DeviceKind},
+		volume_backend::{DeviceInfo, DeviceKind},
 	},
 	application::ActionOptions,
 };
 
 const ICON_SIZE: i32 = 32;
+/// The most segments to show a segmented progress bar with, it looks cramped
+/// when there are too many segments
+const MAX_SEGMENTS: u32 = 4;
 
 /// A window that our application can open that contains the main project view.
 #[derive(Clone, Debug)]
@@ -120,13 +126,16 @@ impl SwayosdWindow {
 	}
 
 	pub fn changed_volume(&self, action_options: &ActionOptions, device: &DeviceInfo) {
-		let max_volume: f64 = (*action_options.max_volume.get()).into();
+		let max_volume: f64 = get_max_volume(action_options, device).into();
+		let volume_scale = *action_options.volume_scale.get();
 		let show_percentage = action_options.show_percentage.get();
 		let duration = action_options.duration.get();
 
 		self.clear_osd();
 
-		let volume = volume_to_f64(&device.volume.avg());
+		// The loudest channel, like when changing the volume, so that the shown
+		// volume doesn't lag behind the steps when the balance isn't centered
+		let volume = volume_to_f64(&device.volume.max());
 		let icon_prefix = match device.kind {
 			DeviceKind::Sink => "sink",
 			DeviceKind::Source => "source",
@@ -146,8 +155,51 @@ impl SwayosdWindow {
 		let icon_name = format!("{}-volume-{}-symbolic", icon_prefix, icon_state);
 
 		let icon = self.build_icon_widget(&icon_name);
-		let progress = self.build_progress_widget(volume / max_volume);
-		let label = self.build_text_widget(&Some(format!("{}%", volume)), Some(4));
+		let fraction = volume_to_fraction(device.volume.max(), max_volume, volume_scale);
+		let progress = self.build_progress_widget(fraction);
+		let label = self.build_text_widget(
+			&Some(format_volume(device.volume.max(), volume_scale)),
+			Some(4),
+		);
+
+		progress.set_sensitive(!device.mute);
+		if volume > 100.0 {
+			progress.add_css_class("overamplified");
+		}
+
+		self.container.append(&icon);
+		self.container.append(&progress);
+		if *show_percentage {
+			self.container.append(&label);
+		}
+		if let Some(battery) = device.battery {
+			self.container.append(&self.build_battery_widget(battery));
+		}
+
+		self.run_timeout(duration);
+	}
+
+	pub fn changed_balance(&self, action_options: &ActionOptions, device: &DeviceInfo) {
+		let show_percentage = action_options.show_percentage.get();
+		let duration = action_options.duration.get();
+
+		self.clear_osd();
+
+		let balance = balance_to_f64(device);
+		let icon_prefix = match device.kind {
+			DeviceKind::Sink => "sink",
+			DeviceKind::Source => "source",
+		};
+		let icon_name = format!("{}-volume-high-symbolic", icon_prefix);
+
+		let icon = self.build_icon_widget(&icon_name);
+		let progress = self.build_balance_widget(balance / 100.0);
+		let label_text = match balance {
+			x if x < 0.0 => format!("L{}", x.abs()),
+			x if x > 0.0 => format!("R{}", x),
+			_ => "C".to_string(),
+		};
+		let label = self.build_text_widget(&Some(label_text), Some(4));
 
 		progress.set_sensitive(!device.mute);
 
@@ -173,13 +225,45 @@ impl SwayosdWindow {
 		let icon_name = "display-brightness-symbolic";
 		let icon = self.build_icon_widget(icon_name);
 
-		let brightness = brightness_backend.get_current() as f64;
-		let max = brightness_backend.get_max() as f64;
-		let progress = self.build_progress_widget(brightness / max);
-		let label = self.build_text_widget(
-			&Some(format!("{}%", (brightness / max * 100.).round() as i32)),
-			Some(4),
-		);
+		let current = brightness_backend.get_current();
+		let max = brightness_backend.get_max();
+		let brightness =
+			brightness_to_percent(current, max, *action_options.brightness_exponent.get());
+		let label =
+			self.build_text_widget(&Some(format!("{}%", brightness.round() as i32)), Some(4));
+
+		self.container.append(&icon);
+		// Devices with only a few brightness levels, like LEDs, are shown in segments
+		if max <= MAX_SEGMENTS {
+			let progress = self.build_segmented_progress_widget(current.min(max), max);
+			self.container.append(&progress);
+		} else {
+			let progress = self.build_progress_widget(brightness / 100.);
+			self.container.append(&progress);
+		}
+		if *show_percentage {
+			self.container.append(&label);
+		}
+
+		self.run_timeout(duration);
+	}
+
+	/// Shows which of the brightness presets is active, counting from one
+	pub fn changed_brightness_preset(
+		&self,
+		action_options: &ActionOptions,
+		preset: u32,
+		n_presets: u32,
+		brightness: u32,
+	) {
+		let show_percentage = action_options.show_percentage.get();
+		let duration = action_options.duration.get();
+
+		self.clear_osd();
+
+		let icon = self.build_icon_widget("display-brightness-symbolic");
+		let progress = self.build_segmented_progress_widget(preset, n_presets);
+		let label = self.build_text_widget(&Some(format!("{}%", brightness)), Some(4));
 
 		self.container.append(&icon);
 		self.container.append(&progress);
@@ -225,8 +309,13 @@ impl SwayosdWindow {
 		let icon = self.build_icon_widget(icon_name);
 		self.container.append(&icon);
 
-		// A segmented progress bar looks cramped when there are too many segments
-		if max < 5 {
+		// Show which keyboard changed
+		if let Some(device_name) = action_options.device_name.get() {
+			let label = self.build_text_widget(&Some(device_name.clone()), None);
+			self.container.append(&label);
+		}
+
+		if max <= MAX_SEGMENTS {
 			let progress = self.build_segmented_progress_widget(value, max);
 			self.container.append(&progress);
 		} else {
@@ -279,6 +368,27 @@ impl SwayosdWindow {
 		self.run_timeout(duration);
 	}
 
+	pub fn changed_auto_brightness(&self, action_options: &ActionOptions, state: bool) {
+		let duration = action_options.duration.get();
+
+		self.clear_osd();
+
+		let on_off_text = match state {
+			true => "On",
+			false => "Off",
+		};
+		let label = self.build_text_widget(&Some(format!("Auto brightness {on_off_text}")), None);
+		label.set_hexpand(true);
+
+		let icon = self.build_icon_widget("display-brightness-symbolic");
+		icon.set_sensitive(state);
+
+		self.container.append(&icon);
+		self.container.append(&label);
+
+		self.run_timeout(duration);
+	}
+
 	pub fn custom_progress(&self, action_options: &ActionOptions, fraction: f64) {
 		let duration = action_options.duration.get();
 		let icon_name = action_options.icon_name.get();
@@ -409,6 +519,25 @@ impl SwayosdWindow {
 		}
 	}
 
+	fn build_battery_widget(&self, battery: u8) -> gtk::Box {
+		let battery = battery.min(100);
+		// Battery icons are available in steps of 10
+		let icon_name = format!("battery-level-{}-symbolic", (battery + 5) / 10 * 10);
+		let icon = cascade! {
+			self.build_icon_widget(&icon_name);
+			..set_pixel_size(ICON_SIZE / 2);
+		};
+		let label = gtk::Label::new(Some(&format!("{}%", battery)));
+
+		cascade! {
+			gtk::Box::new(gtk::Orientation::Horizontal, 4);
+			..add_css_class("battery");
+			..append(&icon);
+			..append(&label);
+			..set_valign(gtk::Align::Center);
+		}
+	}
+
 	fn build_progress_widget(&self, fraction: f64) -> gtk::ProgressBar {
 		cascade! {
 			gtk::ProgressBar::new();
@@ -418,6 +547,26 @@ impl SwayosdWindow {
 		}
 	}
 
+	/// A progress bar that grows from the center, towards the left for negative
+	/// values and towards the right for positive values.
+	fn build_balance_widget(&self, balance: f64) -> gtk::Box {
+		let balance = balance.clamp(-1.0, 1.0);
+		let left = cascade! {
+			self.build_progress_widget((-balance).max(0.0));
+			..set_inverted(true);
+		};
+		let right = self.build_progress_widget(balance.max(0.0));
+
+		cascade! {
+			gtk::Box::new(gtk::Orientation::Horizontal, 0);
+			..add_css_class("balance");
+			..append(&left);
+			..append(&right);
+			..set_valign(gtk::Align::Center);
+			..set_hexpand(true);
+		}
+	}
+
 	fn build_segmented_progress_widget(
 		&self,
 		value: u32,
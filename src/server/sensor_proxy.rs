@@ -0,0 +1,169 @@
+use zbus::{proxy, Connection};
+
+use crate::utils::LightLevelUnit;
+
+#[proxy(
+	default_service = "net.hadess.SensorProxy",
+	default_path = "/net/hadess/SensorProxy",
+	interface = "net.hadess.SensorProxy"
+)]
+pub trait SensorProxy {
+	/// The light level is only updated while claimed
+	#[zbus(name = "ClaimLight")]
+	async fn claim_light(&self) -> zbus::Result<()>;
+
+	#[zbus(property)]
+	fn has_ambient_light(&self) -> zbus::Result<bool>;
+
+	#[zbus(property)]
+	fn light_level(&self) -> zbus::Result<f64>;
+
+	/// Either "lux" or "vendor"
+	#[zbus(property)]
+	fn light_level_unit(&self) -> zbus::Result<String>;
+}
+
+pub struct SensorProxy {}
+
+impl SensorProxy {
+	/// Claims the ambient light sensor, and returns it with the unit of its
+	/// light level. The claim lasts until the connection is closed
+	pub async fn init<'a>(
+		connection: &Connection,
+	) -> zbus::Result<(SensorProxyProxy<'a>, LightLevelUnit)> {
+		let proxy = SensorProxyProxy::builder(connection).build().await?;
+		if !proxy.has_ambient_light().await? {
+			return Err(zbus::Error::Failure(
+				"No ambient light sensor available".to_string(),
+			));
+		}
+		let unit = proxy.light_level_unit().await?;
+		let unit = LightLevelUnit::from_name(&unit)
+			.ok_or_else(|| zbus::Error::Failure(format!("Unknown light level unit '{}'", unit)))?;
+		proxy.claim_light().await?;
+
+		Ok((proxy, unit))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_utils::mock_connection;
+	use async_std::{future::timeout, stream::StreamExt};
+	use std::time::Duration;
+	use zbus::interface;
+
+	const SENSOR_PROXY_PATH: &str = "/net/hadess/SensorProxy";
+
+	struct MockSensorProxy {
+		has_ambient_light: bool,
+		light_level: f64,
+		unit: &'static str,
+		claimed: bool,
+	}
+
+	#[interface(name = "net.hadess.SensorProxy")]
+	impl MockSensorProxy {
+		#[zbus(name = "ClaimLight")]
+		fn claim_light(&mut self) {
+			self.claimed = true;
+		}
+
+		#[zbus(property)]
+		fn has_ambient_light(&self) -> bool {
+			self.has_ambient_light
+		}
+
+		#[zbus(property)]
+		fn light_level(&self) -> f64 {
+			self.light_level
+		}
+
+		#[zbus(property)]
+		fn light_level_unit(&self) -> &str {
+			self.unit
+		}
+	}
+
+	fn sensor(has_ambient_light: bool, unit: &'static str) -> MockSensorProxy {
+		MockSensorProxy {
+			has_ambient_light,
+			light_level: 120.0,
+			unit,
+			claimed: false,
+		}
+	}
+
+	#[test]
+	fn follows_light_level() {
+		async_std::task::block_on(async {
+			let (server, client) =
+				mock_connection(|builder| builder.serve_at(SENSOR_PROXY_PATH, sensor(true, "lux")))
+					.await
+					.unwrap();
+
+			let (proxy, unit) = SensorProxy::init(&client).await.unwrap();
+			assert_eq!(unit, LightLevelUnit::Lux);
+			assert_eq!(proxy.light_level().await.unwrap(), 120.0);
+
+			let mut changed_stream = proxy.receive_light_level_changed().await;
+			let sensor = server
+				.object_server()
+				.interface::<_, MockSensorProxy>(SENSOR_PROXY_PATH)
+				.await
+				.unwrap();
+			assert!(sensor.get().await.claimed);
+			sensor.get_mut().await.light_level = 300.0;
+			sensor
+				.get()
+				.await
+				.light_level_changed(sensor.signal_emitter())
+				.await
+				.unwrap();
+
+			// The stream can start with the current level
+			let changed = timeout(Duration::from_secs(1), async {
+				while let Some(msg) = changed_stream.next().await {
+					if msg.get().await == Ok(300.0) {
+						return true;
+					}
+				}
+				false
+			});
+			assert_eq!(changed.await, Ok(true));
+		});
+	}
+
+	#[test]
+	fn reads_light_level_unit() {
+		async_std::task::block_on(async {
+			let (_server, client) = mock_connection(|builder| {
+				builder.serve_at(SENSOR_PROXY_PATH, sensor(true, "vendor"))
+			})
+			.await
+			.unwrap();
+			let (_, unit) = SensorProxy::init(&client).await.unwrap();
+			assert_eq!(unit, LightLevelUnit::Vendor);
+
+			let (_server, client) = mock_connection(|builder| {
+				builder.serve_at(SENSOR_PROXY_PATH, sensor(true, "candela"))
+			})
+			.await
+			.unwrap();
+			assert!(SensorProxy::init(&client).await.is_err());
+		});
+	}
+
+	#[test]
+	fn fails_without_ambient_light() {
+		async_std::task::block_on(async {
+			let (_server, client) = mock_connection(|builder| {
+				builder.serve_at(SENSOR_PROXY_PATH, sensor(false, "lux"))
+			})
+			.await
+			.unwrap();
+			assert!(SensorProxy::init(&client).await.is_err());
+		});
+	}
+}
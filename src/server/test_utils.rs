@@ -0,0 +1,22 @@
+use std::os::unix::net::UnixStream;
+
+use zbus::{connection::Builder, Connection, Guid};
+
+/// Returns the server and client ends of a private peer-to-peer D-Bus
+/// connection, used for testing against mock services. `setup` serves the
+/// mock objects on the server end, which has to be kept alive.
+pub async fn mock_connection<F>(setup: F) -> zbus::Result<(Connection, Connection)>
+where
+	F: FnOnce(Builder<'static>) -> zbus::Result<Builder<'static>> + Send + 'static,
+{
+	let (server, client) = UnixStream::pair()?;
+	let guid = Guid::generate();
+	// Both ends have to do the handshake at the same time
+	let server = async_std::task::spawn(async move {
+		setup(Builder::unix_stream(server).server(guid)?.p2p())?
+			.build()
+			.await
+	});
+	let client = Builder::unix_stream(client).p2p().build().await?;
+	Ok((server.await?, client))
+}
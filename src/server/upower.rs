@@ -1,4 +1,15 @@
-use zbus::{proxy, Connection};
+use zbus::{proxy, zvariant::OwnedObjectPath, Connection};
+
+#[proxy(
+	default_service = "org.freedesktop.UPower",
+	default_path = "/org/freedesktop/UPower",
+	interface = "org.freedesktop.UPower"
+)]
+pub trait UPower {
+	/// Only available since UPower 1.91
+	#[zbus(name = "EnumerateKbdBacklights")]
+	async fn enumerate_kbd_backlights(&self) -> zbus::Result<Vec<OwnedObjectPath>>;
+}
 
 #[proxy(
 	default_service = "org.freedesktop.UPower",
@@ -11,15 +22,222 @@ pub trait KbdBacklight {
 
 	#[zbus(name = "GetMaxBrightness")]
 	async fn get_max_brightness(&self) -> zbus::Result<i32>;
+
+	#[zbus(name = "GetBrightness")]
+	async fn get_brightness(&self) -> zbus::Result<i32>;
+
+	#[zbus(name = "SetBrightness")]
+	async fn set_brightness(&self, value: i32) -> zbus::Result<()>;
 }
 
 pub struct KbdBacklight {}
 
 impl KbdBacklight {
-	pub async fn init<'a>() -> zbus::Result<KbdBacklightProxy<'a>> {
-		let connection = Connection::system().await?;
-		let proxy = KbdBacklightProxy::builder(&connection).build().await?;
+	/// Returns all keyboard backlights, named by the last segment of their
+	/// object path. Falls back to the default keyboard backlight on UPower
+	/// versions older than 1.91
+	pub async fn init_all<'a>(
+		connection: &Connection,
+	) -> zbus::Result<Vec<(String, KbdBacklightProxy<'a>)>> {
+		let upower = UPowerProxy::new(connection).await?;
+		let paths = match upower.enumerate_kbd_backlights().await {
+			Ok(paths) => paths,
+			Err(zbus::Error::MethodError(name, _, _))
+				if name.as_str() == "org.freedesktop.DBus.Error.UnknownMethod" =>
+			{
+				let proxy = KbdBacklightProxy::builder(connection).build().await?;
+				let name = proxy.inner().path().rsplit('/').next().unwrap_or_default();
+				return Ok(vec![(name.to_string(), proxy)]);
+			}
+			Err(e) => return Err(e),
+		};
+
+		let mut backlights = Vec::with_capacity(paths.len());
+		for path in paths {
+			let name = path.rsplit('/').next().unwrap_or_default().to_string();
+			let proxy = KbdBacklightProxy::builder(connection)
+				.path(path)?
+				.build()
+				.await?;
+			backlights.push((name, proxy));
+		}
+		Ok(backlights)
+	}
+
+	/// Returns the keyboard backlight with the name, or the first one, along
+	/// with its name
+	pub async fn init<'a>(
+		connection: &Connection,
+		name: Option<&str>,
+	) -> zbus::Result<(String, KbdBacklightProxy<'a>)> {
+		let mut backlights = Self::init_all(connection).await?;
+		let index = match name {
+			Some(name) => backlights
+				.iter()
+				.position(|(backlight, _)| backlight == name)
+				.ok_or_else(|| {
+					zbus::Error::Failure(format!("Keyboard backlight '{}' does not exist", name))
+				})?,
+			None if backlights.is_empty() => {
+				return Err(zbus::Error::Failure(
+					"No keyboard backlights available".to_string(),
+				));
+			}
+			None => 0,
+		};
+		Ok(backlights.swap_remove(index))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_utils::mock_connection;
+	use zbus::interface;
+
+	const BACKLIGHT_PATH: &str = "/org/freedesktop/UPower/KbdBacklight";
+
+	struct MockUPower {
+		backlights: Vec<OwnedObjectPath>,
+	}
+
+	#[interface(name = "org.freedesktop.UPower")]
+	impl MockUPower {
+		#[zbus(name = "EnumerateKbdBacklights")]
+		fn enumerate_kbd_backlights(&self) -> Vec<OwnedObjectPath> {
+			self.backlights.clone()
+		}
+	}
+
+	/// UPower older than 1.91, which doesn't have EnumerateKbdBacklights
+	struct MockOldUPower {}
+
+	#[interface(name = "org.freedesktop.UPower")]
+	impl MockOldUPower {
+		#[zbus(property)]
+		fn daemon_version(&self) -> &str {
+			"0.99.20"
+		}
+	}
+
+	struct MockKbdBacklight {
+		brightness: i32,
+	}
+
+	#[interface(name = "org.freedesktop.UPower.KbdBacklight")]
+	impl MockKbdBacklight {
+		#[zbus(name = "GetMaxBrightness")]
+		fn get_max_brightness(&self) -> i32 {
+			3
+		}
+
+		#[zbus(name = "GetBrightness")]
+		fn get_brightness(&self) -> i32 {
+			self.brightness
+		}
+
+		#[zbus(name = "SetBrightness")]
+		fn set_brightness(&mut self, value: i32) {
+			self.brightness = value;
+		}
+	}
+
+	fn path(path: &str) -> OwnedObjectPath {
+		OwnedObjectPath::try_from(path).unwrap()
+	}
+
+	#[test]
+	fn finds_backlights_by_name() {
+		async_std::task::block_on(async {
+			let (_server, client) = mock_connection(|builder| {
+				builder
+					.serve_at(
+						"/org/freedesktop/UPower",
+						MockUPower {
+							backlights: vec![
+								path("/org/freedesktop/UPower/KbdBacklight_0"),
+								path("/org/freedesktop/UPower/KbdBacklight_1"),
+							],
+						},
+					)?
+					.serve_at(
+						"/org/freedesktop/UPower/KbdBacklight_0",
+						MockKbdBacklight { brightness: 1 },
+					)?
+					.serve_at(
+						"/org/freedesktop/UPower/KbdBacklight_1",
+						MockKbdBacklight { brightness: 2 },
+					)
+			})
+			.await
+			.unwrap();
+
+			let names: Vec<String> = KbdBacklight::init_all(&client)
+				.await
+				.unwrap()
+				.into_iter()
+				.map(|(name, _)| name)
+				.collect();
+			assert_eq!(names, ["KbdBacklight_0", "KbdBacklight_1"]);
+
+			let (name, first) = KbdBacklight::init(&client, None).await.unwrap();
+			assert_eq!(name, "KbdBacklight_0");
+			assert_eq!(first.get_brightness().await.unwrap(), 1);
+			let (_, second) = KbdBacklight::init(&client, Some("KbdBacklight_1"))
+				.await
+				.unwrap();
+			assert_eq!(second.get_brightness().await.unwrap(), 2);
+			second.set_brightness(0).await.unwrap();
+			assert_eq!(second.get_brightness().await.unwrap(), 0);
+			assert!(KbdBacklight::init(&client, Some("KbdBacklight_2"))
+				.await
+				.is_err());
+		});
+	}
+
+	#[test]
+	fn falls_back_to_default_backlight() {
+		async_std::task::block_on(async {
+			let (_server, client) = mock_connection(|builder| {
+				builder
+					.serve_at("/org/freedesktop/UPower", MockOldUPower {})?
+					.serve_at(BACKLIGHT_PATH, MockKbdBacklight { brightness: 3 })
+			})
+			.await
+			.unwrap();
+
+			let backlights = KbdBacklight::init_all(&client).await.unwrap();
+			assert_eq!(backlights.len(), 1);
+			assert_eq!(backlights[0].0, "KbdBacklight");
+			assert_eq!(backlights[0].1.get_brightness().await.unwrap(), 3);
+		});
+	}
+
+	#[test]
+	fn returns_other_errors() {
+		async_std::task::block_on(async {
+			// UPower isn't running, only the backlight exists
+			let (_server, client) = mock_connection(|builder| {
+				builder.serve_at(BACKLIGHT_PATH, MockKbdBacklight { brightness: 3 })
+			})
+			.await
+			.unwrap();
+			assert!(KbdBacklight::init_all(&client).await.is_err());
+			assert!(KbdBacklight::init(&client, None).await.is_err());
 
-		Ok(proxy)
+			// UPower is running, but there are no keyboard backlights
+			let (_server, client) = mock_connection(|builder| {
+				builder.serve_at(
+					"/org/freedesktop/UPower",
+					MockUPower {
+						backlights: Vec::new(),
+					},
+				)
+			})
+			.await
+			.unwrap();
+			assert!(KbdBacklight::init_all(&client).await.unwrap().is_empty());
+			assert!(KbdBacklight::init(&client, None).await.is_err());
+		});
 	}
 }
@@ -1,5 +1,6 @@
+use anyhow::Context;
 use gtk::glib::{system_config_dirs, user_config_dir};
-use pulse::volume::Volume;
+use pulse::volume::{Volume, VolumeDB, VolumeLinear};
 use std::{
 	fmt::Debug,
 	fs::{self, File},
@@ -8,9 +9,13 @@ use std::{
 };
 
 use crate::actions::{
-	brightness_backend::{self, BrightnessBackendResult},
-	pulse::{DeviceInfo, DeviceKind, VolumeController},
+	brightness_backend::{
+		self, BrightnessBackend, BrightnessBackendCache, BrightnessBackendResult,
+	},
+	volume_backend::{DeviceInfo, DeviceKind, VolumeBackend},
 };
+use crate::application::ActionOptions;
+use crate::config::user::{AutoBrightnessPoint, BrightnessBackendType, VolumeScale};
 
 #[derive(Clone, Debug)]
 pub struct ActionField<T: Clone + Debug> {
@@ -38,6 +43,11 @@ impl<T: Clone + Debug> ActionField<T> {
 		self.value = value;
 	}
 
+	/// If the value has been set for this action, instead of using the default
+	pub fn is_set(&self) -> bool {
+		self.value.is_some()
+	}
+
 	pub fn reset(&mut self) {
 		self.set(None)
 	}
@@ -156,12 +166,57 @@ pub enum VolumeChangeType {
 	UnMute,
 }
 
+pub enum BalanceChangeType {
+	Left,
+	Right,
+	Center,
+}
+
 pub enum BrightnessChangeType {
 	Raise,
 	Lower,
 	Set,
 }
 
+/// The unit of the ambient light level reported by iio-sensor-proxy
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LightLevelUnit {
+	#[default]
+	Lux,
+	/// Raw sensor values without a fixed range
+	Vendor,
+}
+
+impl LightLevelUnit {
+	pub fn from_name(name: &str) -> Option<Self> {
+		match name {
+			"lux" => Some(Self::Lux),
+			"vendor" => Some(Self::Vendor),
+			_ => None,
+		}
+	}
+}
+
+/// The state of the ambient light auto brightness
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AutoBrightnessState {
+	pub enabled: bool,
+	/// The last ambient light level
+	pub light_level: Option<f64>,
+	pub unit: LightLevelUnit,
+	/// The highest ambient light level seen, used to scale vendor levels
+	pub highest_light_level: f64,
+	/// The last brightness percentage set from the ambient light
+	pub brightness: Option<f64>,
+}
+
+pub enum KbdBacklightChangeType {
+	Raise,
+	Lower,
+	Toggle,
+	Set,
+}
+
 pub fn volume_to_f64(volume: &Volume) -> f64 {
 	let tmp_vol = f64::from(volume.0 - Volume::MUTED.0);
 	(100.0 * tmp_vol / f64::from(Volume::NORMAL.0 - Volume::MUTED.0)).round()
@@ -172,88 +227,462 @@ fn volume_from_f64(volume: f64) -> Volume {
 	Volume((tmp + f64::from(Volume::MUTED.0)) as u32)
 }
 
+/// The lowest volume of the dB scale, anything below is treated as muted
+const VOLUME_MIN_DB: f64 = -60.0;
+
+/// Converts the volume into a value on the given scale.
+/// Percent for the linear and cubic scales, and decibels for the dB scale.
+pub fn volume_to_scale(volume: Volume, scale: VolumeScale) -> f64 {
+	match scale {
+		VolumeScale::Linear => (VolumeLinear::from(volume).0 * 100.0).round(),
+		VolumeScale::Cubic => volume_to_f64(&volume),
+		VolumeScale::Db => VolumeDB::from(volume).0.max(VOLUME_MIN_DB).round(),
+	}
+}
+
+pub fn volume_from_scale(value: f64, scale: VolumeScale) -> Volume {
+	match scale {
+		VolumeScale::Linear => Volume::from(VolumeLinear(value.max(0.0) / 100.0)),
+		VolumeScale::Cubic => volume_from_f64(value.max(0.0)),
+		VolumeScale::Db if value <= VOLUME_MIN_DB => Volume::MUTED,
+		VolumeScale::Db => Volume::from(VolumeDB(value)),
+	}
+}
+
+/// Converts the max volume (in %) on the same scale as the displayed volume.
+/// The dB scale doesn't have a percentage, so it uses the cubic percentage
+/// like pactl and pavucontrol
+pub fn max_volume_from_percent(max_volume: f64, scale: VolumeScale) -> Volume {
+	match scale {
+		VolumeScale::Linear | VolumeScale::Cubic => volume_from_scale(max_volume, scale),
+		VolumeScale::Db => volume_from_f64(max_volume),
+	}
+}
+
+/// The progress fraction of the volume, where 1.0 is the max volume (in %)
+pub fn volume_to_fraction(volume: Volume, max_volume: f64, scale: VolumeScale) -> f64 {
+	let value = volume_to_scale(volume, scale);
+	let max = volume_to_scale(max_volume_from_percent(max_volume, scale), scale);
+	match scale {
+		VolumeScale::Linear | VolumeScale::Cubic => value / max,
+		VolumeScale::Db => (value - VOLUME_MIN_DB) / (max - VOLUME_MIN_DB),
+	}
+}
+
+pub fn format_volume(volume: Volume, scale: VolumeScale) -> String {
+	let value = volume_to_scale(volume, scale);
+	match scale {
+		VolumeScale::Linear | VolumeScale::Cubic => format!("{}%", value),
+		VolumeScale::Db => format!("{}dB", value),
+	}
+}
+
+/// Returns the next multiple of the step above the value
+fn snap_up(value: f64, step: f64) -> f64 {
+	if step <= 0.0 {
+		return value;
+	}
+	(value / step).floor() * step + step
+}
+
+/// Returns the next multiple of the step below the value
+fn snap_down(value: f64, step: f64) -> f64 {
+	if step <= 0.0 {
+		return value;
+	}
+	(value / step).ceil() * step - step
+}
+
+/// `is_repeat` is called with the device when raising the volume, and should
+/// return true if the previous raise of the device happened just before (ex:
+/// when holding the key down), which keeps the volume barrier at 100%.
 pub fn change_device_volume(
-	ctrl: &mut VolumeController,
+	backend: &mut dyn VolumeBackend,
 	kind: DeviceKind,
 	change_type: VolumeChangeType,
-	device_name: &Option<String>,
-	max_volume: f64,
+	action_options: &ActionOptions,
+	is_repeat: impl FnOnce(&DeviceInfo) -> bool,
 	step: Option<String>,
 ) -> Option<DeviceInfo> {
-	let device = match device_name {
-		Some(name) => ctrl.get_device_by_name(kind, name),
-		None => ctrl.get_default_device(kind),
-	};
-	let device = match device {
-		Ok(d) => d,
-		Err(e) => {
-			eprintln!("Error getting device: {}", e);
-			return None;
-		}
-	};
+	let volume_scale = *action_options.volume_scale.get();
+	let volume_barrier = *action_options.volume_barrier.get();
+	let snap_to_step = *action_options.snap_to_step.get();
+	let device_name = action_options.device_name.get();
+
+	let device = get_device(backend, kind, device_name)?;
+	let max_volume: f64 = get_max_volume(action_options, &device).into();
 
 	const VOLUME_CHANGE_DELTA: f64 = 5_f64;
-	let delta = volume_from_f64(
-		step.unwrap_or_default()
-			.parse::<f64>()
-			.unwrap_or(VOLUME_CHANGE_DELTA),
-	);
-	match change_type {
+	const VOLUME_CHANGE_DELTA_DB: f64 = 2_f64;
+	let delta = step
+		.unwrap_or_default()
+		.parse::<f64>()
+		.unwrap_or(match volume_scale {
+			VolumeScale::Linear | VolumeScale::Cubic => VOLUME_CHANGE_DELTA,
+			VolumeScale::Db => VOLUME_CHANGE_DELTA_DB,
+		})
+		* f64::from(*action_options.step_multiplier.get());
+	let current = volume_to_scale(device.volume.max(), volume_scale);
+	let result = match change_type {
 		VolumeChangeType::Raise => {
-			let max_volume = volume_from_f64(max_volume);
-			if let Some(volume) = device.volume.clone().inc_clamp(delta, max_volume) {
-				ctrl.set_volume_by_index(kind, device.index, volume);
+			let mut max_volume = max_volume_from_percent(max_volume, volume_scale);
+			// Stop at 100% before over-amplifying. Going further requires a fresh key press
+			let current_max = device.volume.max();
+			let is_repeat = is_repeat(&device);
+			if volume_barrier
+				&& (current_max < Volume::NORMAL || (current_max == Volume::NORMAL && is_repeat))
+			{
+				max_volume = max_volume.min(Volume::NORMAL);
+			}
+			let target = match snap_to_step {
+				true => snap_up(current, delta),
+				false => current + delta,
+			};
+			let target = volume_from_scale(target, volume_scale).min(max_volume);
+			match device.volume.clone().scale(target) {
+				Some(volume) => backend.set_volume_by_index(kind, device.index, volume),
+				None => Ok(()),
 			}
 		}
 		VolumeChangeType::Lower => {
-			if let Some(volume) = device.volume.clone().decrease(delta) {
-				ctrl.set_volume_by_index(kind, device.index, volume);
+			let target = match snap_to_step {
+				true => snap_down(current, delta),
+				false => current - delta,
+			};
+			let target = volume_from_scale(target, volume_scale);
+			match device.volume.clone().scale(target) {
+				Some(volume) => backend.set_volume_by_index(kind, device.index, volume),
+				None => Ok(()),
 			}
 		}
-		VolumeChangeType::MuteToggle => {
-			ctrl.set_mute_by_index(kind, device.index, !device.mute);
+		VolumeChangeType::MuteToggle => backend.set_mute_by_index(kind, device.index, !device.mute),
+		VolumeChangeType::Mute => backend.set_mute_by_index(kind, device.index, true),
+		VolumeChangeType::UnMute => backend.set_mute_by_index(kind, device.index, false),
+	};
+	if let Err(e) = result {
+		eprintln!("Failed to change the {:?} volume: {}", kind, e);
+	}
+
+	match backend.get_device_by_index(kind, device.index) {
+		Ok(d) => Some(d),
+		Err(e) => {
+			eprintln!("Volume backend error: {}", e);
+			None
 		}
-		VolumeChangeType::Mute => {
-			ctrl.set_mute_by_index(kind, device.index, true);
+	}
+}
+
+/// Returns the max volume of the device. The max volume passed by the client
+/// takes precedence over the first matching device limit in the config
+pub fn get_max_volume(action_options: &ActionOptions, device: &DeviceInfo) -> u8 {
+	if !action_options.max_volume.is_set()
+		&& let Some(limit) = action_options
+			.device_volume_limits
+			.get()
+			.iter()
+			.find(|limit| glob_match(&limit.name, &device.name))
+	{
+		return limit.max_volume;
+	}
+	*action_options.max_volume.get()
+}
+
+/// Matches the text against a pattern where `*` matches any number of
+/// characters and `?` matches a single character
+fn glob_match(pattern: &str, text: &str) -> bool {
+	let pattern: Vec<char> = pattern.chars().collect();
+	let text: Vec<char> = text.chars().collect();
+	let (mut p, mut t) = (0, 0);
+	// The position of the last `*` in the pattern, and of the text when it was reached
+	let mut backtrack: Option<(usize, usize)> = None;
+	while t < text.len() {
+		match pattern.get(p) {
+			Some('*') => {
+				backtrack = Some((p, t));
+				p += 1;
+			}
+			Some(&c) if c == '?' || c == text[t] => {
+				p += 1;
+				t += 1;
+			}
+			_ => match backtrack {
+				// Let the last `*` consume one more character
+				Some((star_p, star_t)) => {
+					backtrack = Some((star_p, star_t + 1));
+					p = star_p + 1;
+					t = star_t + 1;
+				}
+				None => return false,
+			},
 		}
-		VolumeChangeType::UnMute => {
-			ctrl.set_mute_by_index(kind, device.index, false);
+	}
+	pattern[p..].iter().all(|&c| c == '*')
+}
+
+fn get_device(
+	backend: &mut dyn VolumeBackend,
+	kind: DeviceKind,
+	device_name: &Option<String>,
+) -> Option<DeviceInfo> {
+	let device = match device_name {
+		Some(name) => backend.get_device_by_name(kind, name),
+		None => backend.get_default_device(kind),
+	};
+	match device {
+		Ok(d) => Some(d),
+		Err(e) => {
+			eprintln!("Error getting device: {}", e);
+			None
 		}
 	}
+}
 
-	match ctrl.get_device_by_index(kind, device.index) {
+/// Returns the balance of the device in percent, from -100 (left) to 100 (right)
+pub fn balance_to_f64(device: &DeviceInfo) -> f64 {
+	(f64::from(device.volume.get_balance(&device.channel_map)) * 100.0).round()
+}
+
+pub fn change_device_balance(
+	backend: &mut dyn VolumeBackend,
+	kind: DeviceKind,
+	change_type: BalanceChangeType,
+	device_name: &Option<String>,
+	step: Option<String>,
+) -> Option<DeviceInfo> {
+	let device = get_device(backend, kind, device_name)?;
+
+	if !device.channel_map.can_balance() {
+		eprintln!("The channel map of the {:?} doesn't support balance", kind);
+		return None;
+	}
+
+	const BALANCE_CHANGE_DELTA: f64 = 10_f64;
+	let delta = step
+		.unwrap_or_default()
+		.parse::<f64>()
+		.unwrap_or(BALANCE_CHANGE_DELTA);
+	let balance = match change_type {
+		BalanceChangeType::Left => balance_to_f64(&device) - delta,
+		BalanceChangeType::Right => balance_to_f64(&device) + delta,
+		BalanceChangeType::Center => 0.0,
+	};
+
+	let mut volume = device.volume;
+	if volume
+		.set_balance(
+			&device.channel_map,
+			(balance.clamp(-100.0, 100.0) / 100.0) as f32,
+		)
+		.is_some()
+		&& let Err(e) = backend.set_volume_by_index(kind, device.index, &volume)
+	{
+		eprintln!("Failed to change the {:?} balance: {}", kind, e);
+	}
+
+	match backend.get_device_by_index(kind, device.index) {
 		Ok(d) => Some(d),
 		Err(e) => {
-			eprintln!("Pulse Error: {}", e);
+			eprintln!("Volume backend error: {}", e);
 			None
 		}
 	}
 }
 
+/// The brightness backends that were changed, and their device names
+pub type BrightnessBackends = Vec<(Option<String>, Box<dyn BrightnessBackend>)>;
+
+/// Returns the brightness device that belongs to the monitor
+pub fn get_monitor_device_name(
+	action_options: &ActionOptions,
+	monitor_name: &str,
+) -> Option<String> {
+	if let Some(device_name) = action_options.monitor_backlights.get().get(monitor_name) {
+		return Some(device_name.clone());
+	}
+	if let Some(device_name) = brightness_backend::find_monitor_backlight(
+		Path::new(brightness_backend::DRM_PATH),
+		Path::new(brightness_backend::BACKLIGHT_PATH),
+		monitor_name,
+	) {
+		return Some(device_name);
+	}
+	// DDC/CI devices are identified by their connector
+	action_options
+		.brightness_backends
+		.get()
+		.contains(&BrightnessBackendType::DdcUtil)
+		.then(|| monitor_name.to_string())
+}
+
+/// Returns the brightness device, or the device that belongs to the monitor if
+/// only the monitor was specified
+fn get_brightness_device_name(action_options: &ActionOptions) -> Option<String> {
+	match (
+		action_options.device_name.get(),
+		action_options.monitor_name.get(),
+	) {
+		(None, Some(monitor_name)) => get_monitor_device_name(action_options, monitor_name),
+		(device_name, _) => device_name.clone(),
+	}
+}
+
+/// Returns the backends of the brightness devices that should be changed
+pub fn get_brightness_backends(
+	action_options: &ActionOptions,
+	cache: &mut BrightnessBackendCache,
+) -> anyhow::Result<BrightnessBackends> {
+	let backend_types = action_options.brightness_backends.get();
+	let device_name = get_brightness_device_name(action_options);
+
+	if device_name.as_deref() != Some(brightness_backend::ALL_DEVICES) {
+		let backend = cache.get_preferred_backend(device_name.clone(), backend_types)?;
+		return Ok(vec![(device_name, backend)]);
+	}
+
+	// Backlights are tried with all backends except for DDC/CI, and DDC/CI
+	// monitors only with the DDC/CI backend
+	let (ddc_types, backlight_types): (Vec<BrightnessBackendType>, Vec<_>) = backend_types
+		.iter()
+		.partition(|backend_type| **backend_type == BrightnessBackendType::DdcUtil);
+	let mut devices: Vec<(String, BrightnessBackendResult)> =
+		brightness_backend::list_backlight_devices(Path::new(brightness_backend::BACKLIGHT_PATH))
+			.into_iter()
+			.map(|name| {
+				let backend = cache.get_preferred_backend(Some(name.clone()), &backlight_types);
+				(name, backend)
+			})
+			.collect();
+	if !ddc_types.is_empty() {
+		devices.extend(cache.get_ddc_backends());
+	}
+
+	let mut backends: BrightnessBackends = Vec::new();
+	for (name, backend) in devices {
+		match backend {
+			Ok(backend) => backends.push((Some(name), backend)),
+			Err(e) => eprintln!("Could not use the brightness device {}: {}", name, e),
+		}
+	}
+	if backends.is_empty() {
+		anyhow::bail!("No brightness device available");
+	}
+	Ok(backends)
+}
+
 pub fn change_brightness(
 	change_type: BrightnessChangeType,
-	device_name: &Option<String>,
-	min_brightness: u32,
+	action_options: &ActionOptions,
+	cache: &mut BrightnessBackendCache,
+	step: Option<String>,
+) -> anyhow::Result<BrightnessBackends> {
+	let mut backends = get_brightness_backends(action_options, cache)?;
+	change_brightness_backends(&mut backends, change_type, action_options, step)?;
+	Ok(backends)
+}
+
+/// Changes the brightness of the devices, and removes the ones that couldn't
+/// be changed. Only fails if none of them could be changed
+pub fn change_brightness_backends(
+	backends: &mut BrightnessBackends,
+	change_type: BrightnessChangeType,
+	action_options: &ActionOptions,
 	step: Option<String>,
-) -> BrightnessBackendResult {
-	const BRIGHTNESS_CHANGE_DELTA: u8 = 5;
-	let value = step.unwrap_or_default().parse::<u8>();
+) -> anyhow::Result<()> {
+	if let [(_, backend)] = backends.as_mut_slice() {
+		return change_backend_brightness(backend.as_mut(), &change_type, action_options, &step);
+	}
 
-	let mut backend = brightness_backend::get_preferred_backend(device_name.clone())?;
+	backends.retain_mut(|(name, backend)| {
+		match change_backend_brightness(backend.as_mut(), &change_type, action_options, &step) {
+			Ok(()) => true,
+			Err(e) => {
+				eprintln!(
+					"Could not change the brightness of {}: {}",
+					name.as_deref().unwrap_or_default(),
+					e
+				);
+				false
+			}
+		}
+	});
+	if backends.is_empty() {
+		anyhow::bail!("Could not change the brightness of any device");
+	}
+	Ok(())
+}
 
-	match change_type {
-		BrightnessChangeType::Raise => backend.raise(
-			value.unwrap_or(BRIGHTNESS_CHANGE_DELTA) as u32,
-			min_brightness,
-		)?,
-		BrightnessChangeType::Lower => backend.lower(
-			value.unwrap_or(BRIGHTNESS_CHANGE_DELTA) as u32,
-			min_brightness,
-		)?,
-		BrightnessChangeType::Set => backend.set(value? as u32, min_brightness)?,
+fn change_backend_brightness(
+	backend: &mut dyn BrightnessBackend,
+	change_type: &BrightnessChangeType,
+	action_options: &ActionOptions,
+	step: &Option<String>,
+) -> anyhow::Result<()> {
+	let target = get_target_brightness(backend, change_type, action_options, step)?;
+	backend.set_raw(target)
+}
+
+/// Returns the raw brightness that the change would result in. Steps are in
+/// the perceived brightness, where the raw brightness is the perceived
+/// brightness to the power of the exponent
+pub fn get_target_brightness(
+	backend: &mut dyn BrightnessBackend,
+	change_type: &BrightnessChangeType,
+	action_options: &ActionOptions,
+	step: &Option<String>,
+) -> anyhow::Result<u32> {
+	let min = f64::from(*action_options.min_brightness.get());
+	let step_multiplier = *action_options.step_multiplier.get();
+	let snap_to_step = *action_options.snap_to_step.get();
+	let exponent = *action_options.brightness_exponent.get();
+
+	const BRIGHTNESS_CHANGE_DELTA: f64 = 5.0;
+	let value = step.as_deref().unwrap_or_default().parse::<f64>();
+	let delta = value.clone().unwrap_or(BRIGHTNESS_CHANGE_DELTA) * f64::from(step_multiplier);
+
+	let max = backend.get_max();
+	let current_raw = backend.get_current();
+	// Drop the rounding error of the conversion, so that snapping from a
+	// multiple of the step doesn't land on the same value
+	let current = (brightness_to_percent(current_raw, max, exponent) * 1e6).round() / 1e6;
+
+	let target = match change_type {
+		BrightnessChangeType::Raise if snap_to_step => snap_up(current, delta),
+		BrightnessChangeType::Lower if snap_to_step => snap_down(current, delta),
+		BrightnessChangeType::Raise => current + delta,
+		BrightnessChangeType::Lower => current - delta,
+		BrightnessChangeType::Set => value.context("Invalid brightness value")?,
 	};
+	let mut target_raw = brightness_from_percent(target.clamp(min, 100.0), max, exponent);
 
-	Ok(backend)
+	// Always change the brightness by at least one, the steps can be smaller
+	// than one at the low end of the curve
+	match change_type {
+		BrightnessChangeType::Raise if target_raw <= current_raw && current_raw < max => {
+			target_raw = current_raw + 1;
+		}
+		BrightnessChangeType::Lower
+			if target_raw >= current_raw
+				&& current_raw > brightness_from_percent(min, max, exponent) =>
+		{
+			target_raw = current_raw - 1;
+		}
+		_ => {}
+	}
+
+	Ok(target_raw)
+}
+
+/// Converts the raw brightness into the perceived percentage
+pub fn brightness_to_percent(brightness: u32, max: u32, exponent: f64) -> f64 {
+	if max == 0 {
+		return 0.0;
+	}
+	(f64::from(brightness) / f64::from(max)).powf(1.0 / exponent) * 100.0
+}
+
+/// Converts the perceived percentage into the raw brightness
+pub fn brightness_from_percent(percent: f64, max: u32, exponent: f64) -> u32 {
+	((percent / 100.0).clamp(0.0, 1.0).powf(exponent) * f64::from(max)).round() as u32
 }
 
 pub fn get_system_css_path() -> Option<PathBuf> {
@@ -284,3 +713,345 @@ pub fn user_style_path(custom_path: Option<PathBuf>) -> Option<String> {
 	}
 	None
 }
+
+/// Returns the keyboard backlight level that the change results in. `value`
+/// is the step or the level to set, and `last` the level to restore when
+/// toggling the backlight back on
+pub fn get_kbd_backlight_target(
+	change_type: &KbdBacklightChangeType,
+	value: Option<i32>,
+	current: i32,
+	max: i32,
+	last: Option<i32>,
+) -> i32 {
+	const KBD_BACKLIGHT_CHANGE_DELTA: i32 = 1;
+	match change_type {
+		KbdBacklightChangeType::Raise => current + value.unwrap_or(KBD_BACKLIGHT_CHANGE_DELTA),
+		KbdBacklightChangeType::Lower => current - value.unwrap_or(KBD_BACKLIGHT_CHANGE_DELTA),
+		KbdBacklightChangeType::Toggle if current > 0 => 0,
+		KbdBacklightChangeType::Toggle => last.filter(|last| *last > 0).unwrap_or(max),
+		KbdBacklightChangeType::Set => value.unwrap_or(current),
+	}
+	.clamp(0, max)
+}
+
+/// Returns the index of the first preset above the current brightness, or
+/// the first preset when the brightness is already at the last one. The
+/// presets have to be sorted
+pub fn get_next_brightness_preset(presets: &[u32], current: f64) -> Option<usize> {
+	if presets.is_empty() {
+		return None;
+	}
+	// Ignore the rounding of the current brightness
+	Some(
+		presets
+			.iter()
+			.position(|preset| f64::from(*preset) > current + 0.5)
+			.unwrap_or(0),
+	)
+}
+
+/// The ambient light to brightness curve used when none is configured
+pub const DEFAULT_AUTO_BRIGHTNESS_CURVE: &[AutoBrightnessPoint] = &[
+	AutoBrightnessPoint {
+		lux: 0.0,
+		brightness: 10.0,
+	},
+	AutoBrightnessPoint {
+		lux: 50.0,
+		brightness: 30.0,
+	},
+	AutoBrightnessPoint {
+		lux: 300.0,
+		brightness: 60.0,
+	},
+	AutoBrightnessPoint {
+		lux: 1000.0,
+		brightness: 100.0,
+	},
+];
+
+/// Returns the brightness percentage for the ambient light level, linearly
+/// interpolated between the points of the curve
+pub fn auto_brightness_from_lux(curve: &[AutoBrightnessPoint], lux: f64) -> Option<f64> {
+	let mut points = curve.to_vec();
+	points.sort_by(|a, b| a.lux.total_cmp(&b.lux));
+
+	let first = points.first()?;
+	if lux <= first.lux {
+		return Some(first.brightness);
+	}
+	for pair in points.windows(2) {
+		let (low, high) = (&pair[0], &pair[1]);
+		if lux <= high.lux {
+			let fraction = (lux - low.lux) / (high.lux - low.lux);
+			return Some(low.brightness + (high.brightness - low.brightness) * fraction);
+		}
+	}
+	points.last().map(|last| last.brightness)
+}
+
+/// Returns the brightness percentage for the ambient light level. Vendor
+/// levels don't have a fixed range, so they're scaled by the highest level
+/// seen instead of following the lux curve
+pub fn auto_brightness_from_light_level(
+	curve: &[AutoBrightnessPoint],
+	state: &AutoBrightnessState,
+) -> Option<f64> {
+	let level = state.light_level?;
+	match state.unit {
+		LightLevelUnit::Lux => auto_brightness_from_lux(curve, level),
+		LightLevelUnit::Vendor if state.highest_light_level > 0.0 => {
+			Some((level / state.highest_light_level * 100.0).clamp(0.0, 100.0))
+		}
+		LightLevelUnit::Vendor => None,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	struct FakeBacklight {
+		current: u32,
+		max: u32,
+	}
+
+	impl BrightnessBackend for FakeBacklight {
+		fn get_current(&mut self) -> u32 {
+			self.current
+		}
+
+		fn get_max(&mut self) -> u32 {
+			self.max
+		}
+
+		fn set_raw(&mut self, val: u32) -> anyhow::Result<()> {
+			self.current = val.min(self.max);
+			Ok(())
+		}
+	}
+
+	fn target_brightness(
+		current: u32,
+		max: u32,
+		change_type: BrightnessChangeType,
+		step: Option<&str>,
+		action_options: &ActionOptions,
+	) -> u32 {
+		let mut backend = FakeBacklight { current, max };
+		let step = step.map(str::to_string);
+		get_target_brightness(&mut backend, &change_type, action_options, &step).unwrap()
+	}
+
+	#[test]
+	fn target_brightness_linear() {
+		use BrightnessChangeType::*;
+		let mut options = ActionOptions::new();
+		assert_eq!(target_brightness(30, 100, Raise, None, &options), 35);
+		assert_eq!(target_brightness(30, 100, Lower, Some("10"), &options), 20);
+		// Clamped to the min brightness and the max
+		assert_eq!(target_brightness(7, 100, Lower, None, &options), 5);
+		assert_eq!(target_brightness(98, 100, Raise, None, &options), 100);
+		assert_eq!(target_brightness(0, 255, Set, Some("50"), &options), 128);
+		// Always changes by at least one
+		assert_eq!(target_brightness(1, 2, Raise, Some("1"), &options), 2);
+
+		options.snap_to_step.set(Some(true));
+		assert_eq!(target_brightness(47, 100, Raise, None, &options), 50);
+		assert_eq!(target_brightness(47, 100, Lower, None, &options), 45);
+		assert_eq!(target_brightness(30, 100, Raise, None, &options), 35);
+		assert_eq!(target_brightness(30, 100, Lower, None, &options), 25);
+		assert_eq!(target_brightness(29, 100, Raise, None, &options), 30);
+	}
+
+	#[test]
+	fn target_brightness_exponent() {
+		use BrightnessChangeType::*;
+		let mut options = ActionOptions::new();
+		options.brightness_exponent.set(Some(2.0));
+		// 50% perceived is 25% raw
+		assert_eq!(target_brightness(0, 100, Set, Some("50"), &options), 25);
+		assert_eq!(target_brightness(25, 100, Raise, Some("10"), &options), 36);
+		assert_eq!(target_brightness(36, 100, Lower, Some("10"), &options), 25);
+		// The low end of the curve still changes by one
+		assert_eq!(
+			target_brightness(10, 1000, Raise, Some("0.1"), &options),
+			11
+		);
+
+		options.snap_to_step.set(Some(true));
+		// 50% perceived snaps up to 55%, 60% snaps down to 55%
+		assert_eq!(target_brightness(25, 100, Raise, None, &options), 30);
+		assert_eq!(target_brightness(36, 100, Lower, None, &options), 30);
+	}
+
+	#[test]
+	fn volume_limit_above_normal() {
+		// The limit is on the displayed scale
+		let max = max_volume_from_percent(150.0, VolumeScale::Linear);
+		assert_eq!(format_volume(max, VolumeScale::Linear), "150%");
+		assert_eq!(volume_to_fraction(max, 150.0, VolumeScale::Linear), 1.0);
+		let normal = volume_from_scale(100.0, VolumeScale::Linear);
+		let fraction = volume_to_fraction(normal, 150.0, VolumeScale::Linear);
+		assert!((fraction - 100.0 / 150.0).abs() < 0.01);
+
+		let max = max_volume_from_percent(150.0, VolumeScale::Cubic);
+		assert_eq!(format_volume(max, VolumeScale::Cubic), "150%");
+
+		// 150% cubic is +10.6dB
+		let max = max_volume_from_percent(150.0, VolumeScale::Db);
+		assert_eq!(format_volume(max, VolumeScale::Db), "11dB");
+		assert_eq!(volume_to_fraction(max, 150.0, VolumeScale::Db), 1.0);
+		let fraction = volume_to_fraction(Volume::NORMAL, 150.0, VolumeScale::Db);
+		assert!((fraction - 60.0 / 71.0).abs() < 0.01);
+	}
+
+	#[test]
+	fn kbd_backlight_target() {
+		use KbdBacklightChangeType::*;
+		assert_eq!(get_kbd_backlight_target(&Raise, None, 1, 3, None), 2);
+		assert_eq!(get_kbd_backlight_target(&Raise, Some(5), 1, 3, None), 3);
+		assert_eq!(get_kbd_backlight_target(&Lower, None, 1, 3, None), 0);
+		assert_eq!(get_kbd_backlight_target(&Lower, Some(2), 1, 3, None), 0);
+		assert_eq!(get_kbd_backlight_target(&Set, Some(2), 0, 3, None), 2);
+		assert_eq!(get_kbd_backlight_target(&Set, Some(-1), 2, 3, None), 0);
+		assert_eq!(get_kbd_backlight_target(&Set, None, 2, 3, None), 2);
+	}
+
+	#[test]
+	fn kbd_backlight_toggle() {
+		use KbdBacklightChangeType::Toggle;
+		assert_eq!(get_kbd_backlight_target(&Toggle, None, 2, 3, None), 0);
+		// Restores the previous level, or the max level if there is none
+		assert_eq!(get_kbd_backlight_target(&Toggle, None, 0, 3, Some(2)), 2);
+		assert_eq!(get_kbd_backlight_target(&Toggle, None, 0, 3, None), 3);
+		assert_eq!(get_kbd_backlight_target(&Toggle, None, 0, 3, Some(0)), 3);
+		assert_eq!(get_kbd_backlight_target(&Toggle, None, 0, 2, Some(5)), 2);
+	}
+
+	#[test]
+	fn auto_brightness_curve() {
+		let curve = DEFAULT_AUTO_BRIGHTNESS_CURVE;
+		assert_eq!(auto_brightness_from_lux(curve, -5.0), Some(10.0));
+		assert_eq!(auto_brightness_from_lux(curve, 0.0), Some(10.0));
+		assert_eq!(auto_brightness_from_lux(curve, 25.0), Some(20.0));
+		assert_eq!(auto_brightness_from_lux(curve, 300.0), Some(60.0));
+		assert_eq!(auto_brightness_from_lux(curve, 650.0), Some(80.0));
+		assert_eq!(auto_brightness_from_lux(curve, 5000.0), Some(100.0));
+		assert_eq!(auto_brightness_from_lux(&[], 100.0), None);
+
+		// The points don't have to be sorted
+		let curve = [
+			AutoBrightnessPoint {
+				lux: 100.0,
+				brightness: 100.0,
+			},
+			AutoBrightnessPoint {
+				lux: 0.0,
+				brightness: 0.0,
+			},
+		];
+		assert_eq!(auto_brightness_from_lux(&curve, 40.0), Some(40.0));
+	}
+
+	#[test]
+	fn auto_brightness_light_level_unit() {
+		let curve = DEFAULT_AUTO_BRIGHTNESS_CURVE;
+		let mut state = AutoBrightnessState {
+			light_level: Some(25.0),
+			highest_light_level: 50.0,
+			..Default::default()
+		};
+		assert_eq!(auto_brightness_from_light_level(curve, &state), Some(20.0));
+
+		state.unit = LightLevelUnit::Vendor;
+		assert_eq!(auto_brightness_from_light_level(curve, &state), Some(50.0));
+		state.highest_light_level = 0.0;
+		assert_eq!(auto_brightness_from_light_level(curve, &state), None);
+		state.light_level = None;
+		assert_eq!(auto_brightness_from_light_level(curve, &state), None);
+
+		assert_eq!(LightLevelUnit::from_name("lux"), Some(LightLevelUnit::Lux));
+		assert_eq!(
+			LightLevelUnit::from_name("vendor"),
+			Some(LightLevelUnit::Vendor)
+		);
+		assert_eq!(LightLevelUnit::from_name("candela"), None);
+	}
+
+	#[test]
+	fn volume_scale_round_trip() {
+		for scale in [VolumeScale::Linear, VolumeScale::Cubic] {
+			assert_eq!(volume_to_scale(Volume::NORMAL, scale), 100.0);
+			assert_eq!(volume_to_scale(Volume::MUTED, scale), 0.0);
+			assert_eq!(volume_to_scale(volume_from_scale(40.0, scale), scale), 40.0);
+			assert_eq!(volume_from_scale(-10.0, scale), Volume::MUTED);
+		}
+		assert_eq!(volume_to_scale(Volume::NORMAL, VolumeScale::Db), 0.0);
+	}
+
+	#[test]
+	fn volume_scale_negative_db() {
+		let scale = VolumeScale::Db;
+		assert_eq!(
+			volume_to_scale(volume_from_scale(-20.0, scale), scale),
+			-20.0
+		);
+		// Everything at or below the lowest dB value is muted
+		assert_eq!(volume_to_scale(Volume::MUTED, scale), VOLUME_MIN_DB);
+		assert_eq!(volume_from_scale(VOLUME_MIN_DB, scale), Volume::MUTED);
+		assert_eq!(volume_from_scale(-80.0, scale), Volume::MUTED);
+		assert!(volume_from_scale(-6.0, scale) < Volume::NORMAL);
+		assert!(volume_from_scale(6.0, scale) > Volume::NORMAL);
+	}
+
+	#[test]
+	fn glob_match_wildcards() {
+		assert!(glob_match("*", "alsa_output.usb"));
+		assert!(glob_match("", ""));
+		assert!(!glob_match("", "alsa_output.usb"));
+		assert!(glob_match("alsa_output.*", "alsa_output.usb"));
+		assert!(glob_match("bluez_output.??", "bluez_output.a2"));
+		assert!(!glob_match("bluez_output.?", "bluez_output.a2"));
+		assert!(glob_match("*usb*", "alsa_output.usb-headset"));
+		assert!(!glob_match("*usb*", "alsa_output.pci"));
+	}
+
+	#[test]
+	fn glob_match_backtracks() {
+		// The `*` has to consume more than the first match
+		assert!(glob_match("*ab", "aab"));
+		assert!(glob_match("a*b*c", "axbxbyc"));
+		assert!(glob_match("*.monitor", "sink.monitor.monitor"));
+		assert!(!glob_match("a*c", "abcbd"));
+		assert!(glob_match("a**", "a"));
+	}
+
+	#[test]
+	fn snap_to_step_multiples() {
+		assert_eq!(snap_up(42.0, 5.0), 45.0);
+		assert_eq!(snap_up(45.0, 5.0), 50.0);
+		assert_eq!(snap_down(42.0, 5.0), 40.0);
+		assert_eq!(snap_down(45.0, 5.0), 40.0);
+		// Negative values, ex: on the dB scale
+		assert_eq!(snap_up(-13.0, 2.0), -12.0);
+		assert_eq!(snap_down(-12.0, 2.0), -14.0);
+		// Invalid steps don't change the value
+		assert_eq!(snap_up(42.0, 0.0), 42.0);
+		assert_eq!(snap_down(42.0, -5.0), 42.0);
+	}
+
+	#[test]
+	fn next_brightness_preset() {
+		let presets = [10, 40, 70, 100];
+		assert_eq!(get_next_brightness_preset(&[], 50.0), None);
+		assert_eq!(get_next_brightness_preset(&presets, 0.0), Some(0));
+		assert_eq!(get_next_brightness_preset(&presets, 25.0), Some(1));
+		// The current brightness is rounded, so being close to a preset counts as on it
+		assert_eq!(get_next_brightness_preset(&presets, 40.4), Some(2));
+		// Wraps around after the last preset
+		assert_eq!(get_next_brightness_preset(&presets, 100.0), Some(0));
+		assert_eq!(get_next_brightness_preset(&[50], 50.0), Some(0));
+	}
+}